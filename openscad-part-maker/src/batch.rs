@@ -0,0 +1,446 @@
+//! `batch` subcommand: render a run of parts from a JSON job list, and the
+//! single-part `render` subcommand built on the same jobs.
+
+use anyhow::Context;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::format::ExportFormat;
+use crate::prelude::*;
+use crate::render::{PreviewOptions, Quality, RenderOptions, Renderer};
+use crate::scad_params::{
+    ScadParamTemplate, ScadParams, json_value_to_field, sanitize_filename_component,
+};
+
+/// One entry of the jobs file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Job {
+    /// SVG to import; relative paths are resolved against the jobs file.
+    pub svg: PathBuf,
+    /// Output base name, also passed as `NAME`. Defaults to the SVG file stem.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Param overrides keyed by form field name (e.g. `coaster_d`).
+    #[serde(default)]
+    pub params: BTreeMap<String, serde_json::Value>,
+    #[serde(default = "default_fs")]
+    pub fs: f32,
+    #[serde(default = "default_fa")]
+    pub fa: f32,
+    #[serde(default = "default_fn", rename = "fn")]
+    pub fn_: i32,
+}
+
+impl Job {
+    /// A job for `svg` with no overrides: default name, params and quality.
+    pub fn new(svg: impl Into<PathBuf>) -> Self {
+        Self {
+            svg: svg.into(),
+            name: None,
+            params: BTreeMap::new(),
+            fs: default_fs(),
+            fa: default_fa(),
+            fn_: default_fn(),
+        }
+    }
+}
+
+fn default_fs() -> f32 {
+    0.1
+}
+
+fn default_fa() -> f32 {
+    5.0
+}
+
+fn default_fn() -> i32 {
+    200
+}
+
+/// The jobs file is either a bare list or `{"jobs": [...]}`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JobsFile {
+    List(Vec<Job>),
+    Wrapped { jobs: Vec<Job> },
+}
+
+/// Parse the contents of a jobs file.
+pub fn parse_jobs(text: &str) -> anyhow::Result<Vec<Job>> {
+    let file: JobsFile = serde_json::from_str(text).context("parse jobs file")?;
+    Ok(match file {
+        JobsFile::List(jobs) => jobs,
+        JobsFile::Wrapped { jobs } => jobs,
+    })
+}
+
+/// A job resolved against the template: validated params and file paths.
+#[derive(Debug, Clone)]
+pub struct PreparedJob {
+    pub name: String,
+    pub svg_path: PathBuf,
+    pub stl_path: PathBuf,
+    pub params: ScadParams,
+    pub quality: Quality,
+}
+
+/// Validate a job's params against the template and resolve its paths.
+pub fn prepare_job(
+    template: &ScadParamTemplate,
+    job: &Job,
+    base_dir: &Path,
+    out_dir: &Path,
+) -> anyhow::Result<PreparedJob> {
+    let svg_path = base_dir.join(&job.svg);
+    let name = match &job.name {
+        Some(name) if !name.trim().is_empty() => name.clone(),
+        _ => svg_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "output".to_string()),
+    };
+
+    let mut params = template.instantiate();
+    for (field, value) in &job.params {
+        let text = json_value_to_field(value);
+        params.set_from_field(field, &text)?;
+    }
+    params.set_string("NAME", &name);
+
+    let stl_path = out_dir.join(format!("{}.stl", sanitize_filename_component(&name)));
+
+    Ok(PreparedJob {
+        name,
+        svg_path,
+        stl_path,
+        params,
+        quality: Quality {
+            fs: job.fs,
+            fa: job.fa,
+            fn_: job.fn_,
+        },
+    })
+}
+
+/// Render one prepared job and write the STL to its output path.
+async fn render_job(renderer: &Renderer, job: &PreparedJob) -> anyhow::Result<()> {
+    let svg = tokio::fs::read(&job.svg_path)
+        .await
+        .with_context(|| format!("read {}", job.svg_path.display()))?;
+    let stl = renderer
+        .render_with(&svg, &job.params, ExportFormat::Stl, job.quality)
+        .await?;
+    tokio::fs::write(&job.stl_path, stl)
+        .await
+        .with_context(|| format!("write {}", job.stl_path.display()))?;
+    Ok(())
+}
+
+/// Where the `render` subcommand puts its STL: `-o -` means stdout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderOutput {
+    File(PathBuf),
+    Stdout,
+}
+
+impl RenderOutput {
+    pub fn parse(arg: &str) -> Self {
+        match arg {
+            "-" => RenderOutput::Stdout,
+            path => RenderOutput::File(PathBuf::from(path)),
+        }
+    }
+}
+
+/// Render a single job to `output`, or to `<name>.stl` in the current
+/// directory. With `RenderOutput::Stdout` the STL bytes are the only thing
+/// written to `stdout`; OpenSCAD can't write to a pipe, so the render goes to
+/// a temp file that is then copied over.
+pub async fn render_one(
+    renderer: &Renderer,
+    job: &Job,
+    output: Option<RenderOutput>,
+    stdout: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let prepared = prepare_job(&renderer.template(), job, Path::new("."), Path::new("."))?;
+    let output = output.unwrap_or_else(|| RenderOutput::File(prepared.stl_path.clone()));
+    let svg = tokio::fs::read(&prepared.svg_path)
+        .await
+        .with_context(|| format!("read {}", prepared.svg_path.display()))?;
+
+    let tmpdir = tempfile::tempdir().context("create render temp dir")?;
+    let rendered = renderer
+        .render_to_file(
+            tmpdir.path(),
+            &svg,
+            &prepared.params,
+            ExportFormat::Stl,
+            prepared.quality,
+            &PreviewOptions::default(),
+        )
+        .await?;
+    match &output {
+        RenderOutput::Stdout => {
+            let mut file = std::fs::File::open(&rendered.path)
+                .with_context(|| format!("open {}", rendered.path.display()))?;
+            std::io::copy(&mut file, stdout).context("write STL to stdout")?;
+            stdout.flush().context("write STL to stdout")?;
+        }
+        RenderOutput::File(path) => {
+            std::fs::copy(&rendered.path, path)
+                .with_context(|| format!("write {}", path.display()))?;
+            let _ = writeln!(stdout, "ok    {} -> {}", prepared.name, path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of a batch run.
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub succeeded: Vec<(String, PathBuf)>,
+    pub failed: Vec<(String, String)>,
+}
+
+impl BatchSummary {
+    pub fn write_report(&self, out: &mut dyn Write) {
+        for (name, path) in &self.succeeded {
+            let _ = writeln!(out, "ok    {name} -> {}", path.display());
+        }
+        for (name, err) in &self.failed {
+            let _ = writeln!(out, "FAIL  {name}: {err}");
+        }
+        let _ = writeln!(
+            out,
+            "{} succeeded, {} failed",
+            self.succeeded.len(),
+            self.failed.len()
+        );
+    }
+}
+
+/// Split off the jobs whose output file an earlier job already writes (their
+/// names are equal once sanitized), failing them instead of letting the
+/// last one silently win.
+fn split_duplicate_outputs(jobs: Vec<PreparedJob>) -> (Vec<PreparedJob>, Vec<(String, String)>) {
+    let mut outputs = HashSet::new();
+    let (unique, duplicates): (Vec<_>, Vec<_>) = jobs
+        .into_iter()
+        .partition(|job| outputs.insert(job.stl_path.clone()));
+    let failed = duplicates
+        .into_iter()
+        .map(|job| {
+            let err = format!(
+                "an earlier job already writes {}; give the jobs distinct names",
+                job.stl_path.display()
+            );
+            (job.name, err)
+        })
+        .collect();
+    (unique, failed)
+}
+
+/// Render every job in `jobs_path` with `openscad_bin`, at most
+/// `concurrency` at a time. `lib_paths` are searched for includes, as with
+/// `serve --scad-lib-path`.
+pub async fn run(
+    openscad_bin: &Path,
+    lib_paths: &[PathBuf],
+    input_scad_path: &Path,
+    jobs_path: &Path,
+    out_dir: &Path,
+    concurrency: usize,
+) -> anyhow::Result<BatchSummary> {
+    let template = ScadParamTemplate::from_scad_tree_with_libs(input_scad_path, lib_paths)?;
+    let mut options = RenderOptions::new(input_scad_path);
+    options.lib_paths = lib_paths.to_vec();
+    let renderer = Arc::new(Renderer::new(openscad_bin, template, options));
+    let text = std::fs::read_to_string(jobs_path)
+        .with_context(|| format!("read {}", jobs_path.display()))?;
+    let jobs = parse_jobs(&text)?;
+    let base_dir = jobs_path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(out_dir).with_context(|| format!("create {}", out_dir.display()))?;
+
+    let mut summary = BatchSummary::default();
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    let mut prepared_jobs = Vec::new();
+    for (idx, job) in jobs.iter().enumerate() {
+        match prepare_job(&renderer.template(), job, base_dir, out_dir) {
+            Ok(p) => prepared_jobs.push(p),
+            Err(err) => summary
+                .failed
+                .push((format!("job {}", idx + 1), err.to_string())),
+        }
+    }
+    let (prepared_jobs, duplicates) = split_duplicate_outputs(prepared_jobs);
+    summary.failed.extend(duplicates);
+
+    for prepared in prepared_jobs {
+        let semaphore = semaphore.clone();
+        let renderer = renderer.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            info!("Rendering {}...", prepared.name);
+            let result = match render_job(&renderer, &prepared).await {
+                Ok(()) => Ok(prepared.stl_path),
+                Err(err) => Err(format!("{err:#}")),
+            };
+            (prepared.name, result)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let (name, result) = joined.context("batch task panicked")?;
+        match result {
+            Ok(path) => summary.succeeded.push((name, path)),
+            Err(err) => summary.failed.push((name, err)),
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::DEFAULT_OPENSCAD_BIN;
+
+    fn template(scad: &str) -> ScadParamTemplate {
+        ScadParamTemplate::from_scad_text(scad).unwrap()
+    }
+
+    #[test]
+    fn parse_jobs_accepts_list_and_wrapped_forms() {
+        let list = r#"[
+            {"svg": "a.svg", "name": "Alice", "params": {"coaster_d": 90, "use_spinner": false}},
+            {"svg": "b.svg", "fn": 64}
+        ]"#;
+        let jobs = parse_jobs(list).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].name.as_deref(), Some("Alice"));
+        assert_eq!(jobs[0].params["coaster_d"], serde_json::json!(90));
+        assert_eq!(jobs[0].fn_, 200);
+        assert_eq!(jobs[1].name, None);
+        assert_eq!(jobs[1].fn_, 64);
+
+        let wrapped = r#"{"jobs": [{"svg": "c.svg"}]}"#;
+        let jobs = parse_jobs(wrapped).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].svg, PathBuf::from("c.svg"));
+    }
+
+    #[test]
+    fn render_output_dash_means_stdout() {
+        assert_eq!(RenderOutput::parse("-"), RenderOutput::Stdout);
+        assert_eq!(
+            RenderOutput::parse("part.stl"),
+            RenderOutput::File(PathBuf::from("part.stl"))
+        );
+    }
+
+    #[tokio::test]
+    async fn render_one_to_stdout_writes_only_the_stl() {
+        let dir = tempfile::tempdir().unwrap();
+        let svg = dir.path().join("logo.svg");
+        std::fs::write(&svg, crate::render::PLACEHOLDER_SVG).unwrap();
+        let mut options = RenderOptions::new(dir.path().join("in.scad"));
+        options.fake = true;
+        let renderer = Renderer::new(DEFAULT_OPENSCAD_BIN, template(""), options);
+        let job: Job = serde_json::from_value(serde_json::json!({ "svg": svg })).unwrap();
+
+        let mut stdout = Vec::new();
+        render_one(&renderer, &job, Some(RenderOutput::Stdout), &mut stdout)
+            .await
+            .unwrap();
+        assert_eq!(stdout, crate::stl::single_triangle());
+
+        let path = dir.path().join("out.stl");
+        let output = Some(RenderOutput::File(path.clone()));
+        let mut stdout = Vec::new();
+        render_one(&renderer, &job, output, &mut stdout)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), crate::stl::single_triangle());
+        let report = String::from_utf8(stdout).unwrap();
+        assert!(report.starts_with("ok    logo -> "), "{report}");
+    }
+
+    #[test]
+    fn parse_jobs_rejects_missing_svg() {
+        assert!(parse_jobs(r#"[{"name": "x"}]"#).is_err());
+    }
+
+    #[test]
+    fn prepare_job_resolves_params_and_paths() {
+        let tmpl = template(
+            r#"
+NAME="output"; // @param
+COASTER_D=101.6; // @param
+USE_SPINNER=true; // @param
+"#,
+        );
+        let job = parse_jobs(
+            r#"[{"svg": "logos/a.svg", "params": {"coaster_d": 90, "use_spinner": false}}]"#,
+        )
+        .unwrap()
+        .remove(0);
+
+        let prepared = prepare_job(&tmpl, &job, Path::new("/jobs"), Path::new("/out")).unwrap();
+
+        assert_eq!(prepared.name, "a");
+        assert_eq!(prepared.svg_path, PathBuf::from("/jobs/logos/a.svg"));
+        assert_eq!(prepared.stl_path, PathBuf::from("/out/a.stl"));
+        assert_eq!(prepared.quality.fn_, 200);
+        let defines: Vec<String> = prepared.params.iter_defines().collect();
+        assert!(defines.contains(&"COASTER_D=90.0".to_string()));
+        assert!(defines.contains(&"USE_SPINNER=false".to_string()));
+        assert!(defines.contains(&"NAME=\"a\"".to_string()));
+    }
+
+    #[test]
+    fn jobs_writing_the_same_file_are_failed() {
+        let tmpl = template("NAME=\"output\"; // @param\n");
+        let jobs = parse_jobs(
+            r#"[
+            {"svg": "a.svg", "name": "Coaster"},
+            {"svg": "b.svg"},
+            {"svg": "c.svg", "name": "Coaster"},
+            {"svg": "logos/b.svg"}
+        ]"#,
+        )
+        .unwrap();
+        let prepared = jobs
+            .iter()
+            .map(|job| prepare_job(&tmpl, job, Path::new("/jobs"), Path::new("/out")).unwrap())
+            .collect();
+
+        let (unique, failed) = split_duplicate_outputs(prepared);
+        let svgs: Vec<_> = unique.iter().map(|job| job.svg_path.clone()).collect();
+        assert_eq!(
+            svgs,
+            [PathBuf::from("/jobs/a.svg"), PathBuf::from("/jobs/b.svg")]
+        );
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0].0, "Coaster");
+        assert!(failed[0].1.contains("/out/Coaster.stl"), "{}", failed[0].1);
+        assert_eq!(failed[1].0, "b");
+    }
+
+    #[test]
+    fn prepare_job_rejects_invalid_param() {
+        let tmpl = template("COASTER_D=101.6; // @param\n");
+        let job = parse_jobs(r#"[{"svg": "a.svg", "params": {"coaster_d": "big"}}]"#)
+            .unwrap()
+            .remove(0);
+        let err = prepare_job(&tmpl, &job, Path::new("."), Path::new(".")).unwrap_err();
+        assert!(err.to_string().contains("coaster_d"));
+    }
+}
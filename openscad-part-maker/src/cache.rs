@@ -0,0 +1,121 @@
+//! Finished renders kept for identical repeat requests (`--render-cache`).
+//! `GET /render` and `POST /render` share one cache, keyed by a fingerprint
+//! of the validated form, so a demo link and a form submission with the same
+//! params run OpenSCAD once between them.
+
+use std::{collections::HashMap, sync::Mutex};
+
+#[derive(Debug)]
+struct Entry<T> {
+    value: T,
+    /// `Entries::tick` at the last hit or insert, for LRU eviction.
+    used: u64,
+}
+
+#[derive(Debug)]
+struct Entries<T> {
+    map: HashMap<u64, Entry<T>>,
+    tick: u64,
+}
+
+/// Fingerprint -> render map holding at most `capacity` entries; the least
+/// recently used is dropped to make room. A capacity of 0 disables it.
+#[derive(Debug)]
+pub struct RenderCache<T> {
+    entries: Mutex<Entries<T>>,
+    capacity: usize,
+}
+
+impl<T: Clone> RenderCache<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                tick: 0,
+            }),
+            capacity,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// The render stored for `key`, marking it recently used.
+    pub fn get(&self, key: u64) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+        let entry = entries.map.get_mut(&key)?;
+        entry.used = tick;
+        Some(entry.value.clone())
+    }
+
+    pub fn insert(&self, key: u64, value: T) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let used = entries.tick;
+        if !entries.map.contains_key(&key) && entries.map.len() >= self.capacity {
+            let oldest = entries
+                .map
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(k, _)| *k);
+            if let Some(oldest) = oldest {
+                entries.map.remove(&oldest);
+            }
+        }
+        entries.map.insert(key, Entry { value, used });
+    }
+
+    /// Drop everything, e.g. when the template the renders came from changes.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().map.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_is_dropped_when_full() {
+        let cache = RenderCache::new(2);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        // A hit makes 2 the least recently used.
+        assert_eq!(cache.get(1), Some("a"));
+        cache.insert(3, "c");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(1), Some("a"));
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.get(3), Some("c"));
+
+        // Replacing a key doesn't evict another.
+        cache.insert(3, "c2");
+        assert_eq!(cache.get(1), Some("a"));
+        assert_eq!(cache.get(3), Some("c2"));
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn zero_capacity_stores_nothing() {
+        let cache = RenderCache::new(0);
+        cache.insert(1, "a");
+        assert!(!cache.is_enabled());
+        assert_eq!(cache.get(1), None);
+    }
+}
@@ -21,10 +21,41 @@ pub fn app() -> Command {
                 .help("Sets the log level to debug.")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .help("Only log warnings and errors, and skip the blank lines around output.")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .global(true)
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Log line format: human-readable text or one JSON object per line."),
+        )
         .subcommand(
             Command::new("hello")
                 .about("Greeting")
-                .arg(Arg::new("NAME").default_value("Bob")),
+                .arg(Arg::new("NAME").default_value("Bob"))
+                .arg(
+                    Arg::new("repeat")
+                        .long("repeat")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32))
+                        .default_value("1")
+                        .help("Print the greeting N times"),
+                )
+                .arg(
+                    Arg::new("uppercase")
+                        .long("uppercase")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Shout the greeting"),
+                ),
         )
         .subcommand(
             Command::new("completions")
@@ -33,7 +64,7 @@ pub fn app() -> Command {
                     Arg::new("shell")
                         .help("The shell to generate completions for")
                         .required(false)
-                        .value_parser(["bash", "zsh", "fish"]),
+                        .value_parser(["bash", "zsh", "fish", "powershell", "elvish"]),
                 ),
         )
         .subcommand(
@@ -44,14 +75,549 @@ pub fn app() -> Command {
                         .long("listen")
                         .value_name("ADDR")
                         .default_value("127.0.0.1:3000")
-                        .help("Address to bind the HTTP server to"),
+                        .help("Address to bind the HTTP server to (HOST:PORT, or just PORT for 0.0.0.0)"),
+                )
+                .arg(
+                    Arg::new("port-file")
+                        .long("port-file")
+                        .value_name("PATH")
+                        .help("Write the bound address to PATH after binding (use with port 0)"),
+                )
+                .arg(
+                    Arg::new("tls-cert")
+                        .long("tls-cert")
+                        .value_name("PEM")
+                        .help("Serve HTTPS with this certificate chain (needs --tls-key)"),
+                )
+                .arg(
+                    Arg::new("tls-key")
+                        .long("tls-key")
+                        .value_name("PEM")
+                        .help("Private key for --tls-cert"),
+                )
+                .arg(
+                    Arg::new("base-path")
+                        .long("base-path")
+                        .value_name("PREFIX")
+                        .default_value("")
+                        .help("Mount every route under this URL prefix (e.g. /coasters) for a reverse proxy"),
+                )
+                .arg(
+                    Arg::new("title")
+                        .long("title")
+                        .value_name("TEXT")
+                        .help("Form page title and heading"),
+                )
+                .arg(
+                    Arg::new("subtitle")
+                        .long("subtitle")
+                        .value_name("TEXT")
+                        .help("Text under the form page heading"),
+                )
+                .arg(
+                    Arg::new("theme")
+                        .long("theme")
+                        .value_name("THEME")
+                        .value_parser(["auto", "dark", "light"])
+                        .default_value("auto")
+                        .help("Form page color scheme; auto follows the visitor's OS setting"),
+                )
+                .arg(
+                    Arg::new("coordinate-system")
+                        .long("coordinate-system")
+                        .value_name("AXES")
+                        .value_parser(["zup", "yup"])
+                        .default_value("zup")
+                        .help("Which axis points up in STL output; yup for tools that expect Y up"),
+                )
+                .arg(
+                    Arg::new("input-scad")
+                        .long("input-scad")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Path to input.scad template file"),
+                )
+                .arg(
+                    Arg::new("max-fn")
+                        .long("max-fn")
+                        .value_name("N")
+                        .default_value("2000")
+                        .value_parser(clap::value_parser!(i32).range(1..))
+                        .help("Reject fn/seg values above this segment count"),
+                )
+                .arg(
+                    Arg::new("deterministic")
+                        .long("deterministic")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Render single-threaded and canonicalize STL output (slower, reproducible)"),
+                )
+                .arg(
+                    Arg::new("preflight")
+                        .long("preflight")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Test-render the template with default params before serving"),
+                )
+                .arg(
+                    Arg::new("preflight-timeout")
+                        .long("preflight-timeout")
+                        .value_name("SECONDS")
+                        .default_value("60")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("Fail the preflight render if it takes longer than this"),
+                )
+                .arg(
+                    Arg::new("strict")
+                        .long("strict")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Fail renders on OpenSCAD warnings (--hardwarnings, plus parameter checks on 2021.01+)"),
+                )
+                .arg(
+                    Arg::new("param-prefix")
+                        .long("param-prefix")
+                        .value_name("PREFIX")
+                        .default_value("")
+                        .help("Prefix for template param field names (e.g. scad_), for embedding the form in another page"),
+                )
+                .arg(
+                    Arg::new("render-threads")
+                        .long("render-threads")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help("Threads per render (sets OPENSCAD_THREADS for OpenSCAD)"),
+                )
+                .arg(
+                    Arg::new("render-nice")
+                        .long("render-nice")
+                        .value_name("LEVEL")
+                        .value_parser(clap::value_parser!(i32).range(-20..=19))
+                        .help("Run renders at this niceness (Unix only; 19 is the lowest priority)"),
+                )
+                .arg(
+                    Arg::new("render-mem-limit")
+                        .long("render-mem-limit")
+                        .value_name("MIB")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("Cap each render's address space at MIB mebibytes (Unix only); renders over it fail with 507"),
+                )
+                .arg(
+                    Arg::new("sandbox-cmd")
+                        .long("sandbox-cmd")
+                        .value_name("COMMAND")
+                        .help("Run OpenSCAD under this wrapper, e.g. 'bwrap --ro-bind / / --bind {workdir} {workdir} --'; {workdir} is the render's temp dir and {template_dir} the template's directory"),
+                )
+                .arg(
+                    Arg::new("allow-svg-dir")
+                        .long("allow-svg-dir")
+                        .value_name("DIR")
+                        .help("Let requests name an SVG in DIR (svg_asset field) instead of uploading one"),
+                )
+                .arg(
+                    Arg::new("filename-template")
+                        .long("filename-template")
+                        .value_name("PATTERN")
+                        .help("Download name, e.g. \"{name}-{mode}-{date}.{ext}\"; placeholders are name, ext, date (UTC) and any param"),
+                )
+                .arg(
+                    Arg::new("format-map")
+                        .long("format-map")
+                        .value_name("EXT=MIME")
+                        .action(clap::ArgAction::Append)
+                        .help("Content-Type for an export format's downloads, e.g. stl=application/sla (repeatable)"),
+                )
+                .arg(
+                    Arg::new("allowed-formats")
+                        .long("allowed-formats")
+                        .value_name("EXTS")
+                        .value_delimiter(',')
+                        .action(clap::ArgAction::Append)
+                        .help("Comma-separated export formats requests may ask for, e.g. stl,svg (default: all)"),
+                )
+                .arg(
+                    Arg::new("enable-feature")
+                        .long("enable-feature")
+                        .value_name("FEATURE")
+                        .action(clap::ArgAction::Append)
+                        .help("Turn on an experimental OpenSCAD feature, e.g. lazy-union (repeatable)"),
+                )
+                .arg(
+                    Arg::new("admin-token")
+                        .long("admin-token")
+                        .value_name("TOKEN")
+                        .help("Enable POST /admin/reload for requests sending Authorization: Bearer TOKEN"),
+                )
+                .arg(
+                    Arg::new("on-render")
+                        .long("on-render")
+                        .value_name("WEBHOOK_URL")
+                        .help("POST a JSON event (filename, bytes, params, duration_ms) to this http:// URL after each render"),
+                )
+                .arg(
+                    Arg::new("env-file")
+                        .long("env-file")
+                        .value_name("PATH")
+                        .help("KEY=VALUE file of environment variables (e.g. OPENSCADPATH) for OpenSCAD"),
+                )
+                .arg(
+                    Arg::new("scad-lib-path")
+                        .long("scad-lib-path")
+                        .value_name("DIR")
+                        .action(clap::ArgAction::Append)
+                        .help("Library directory for include <...> / use <...>, added to OpenSCADPATH (repeatable)"),
+                )
+                .arg(
+                    Arg::new("print-config")
+                        .long("print-config")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Print the resolved configuration (flags and environment merged) as JSON and exit"),
+                )
+                .arg(
+                    Arg::new("no-index")
+                        .long("no-index")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("API-only mode: don't serve the HTML form at / (or its static assets)"),
+                )
+                .arg(
+                    Arg::new("fake-render")
+                        .long("fake-render")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Skip OpenSCAD and return a single-triangle placeholder (for UI development)"),
+                )
+                .arg(
+                    Arg::new("expose-errors")
+                        .long("expose-errors")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Return OpenSCAD stderr and argv (X-OpenSCAD-Args) to clients for debugging"),
+                )
+                .arg(
+                    Arg::new("read-only-template")
+                        .long("read-only-template")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Refuse any render whose temp dir is inside the template's directory"),
+                )
+                .arg(
+                    Arg::new("max-renders")
+                        .long("max-renders")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("Run at most N OpenSCAD renders at once; further requests wait for a free slot"),
+                )
+                .arg(
+                    Arg::new("max-render-per-minute")
+                        .long("max-render-per-minute")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help("Cap renders across all clients at N per minute; beyond that answer 503 with Retry-After"),
+                )
+                .arg(
+                    Arg::new("render-cache")
+                        .long("render-cache")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Keep the last N renders and answer identical GET/POST /render requests from them"),
+                )
+                .arg(
+                    Arg::new("render-timeout")
+                        .long("render-timeout")
+                        .value_name("SECS")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .help("Kill renders that take longer than this many seconds"),
+                )
+                .arg(
+                    Arg::new("slow-render-ms")
+                        .long("slow-render-ms")
+                        .value_name("MS")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("10000")
+                        .help("Log a warning with the params for renders slower than this (0 disables)"),
+                )
+                .arg(
+                    Arg::new("render-retries")
+                        .long("render-retries")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u32))
+                        .default_value("0")
+                        .help("Retry renders that fail transiently (spawn errors, OOM kills) up to N times"),
+                )
+                .arg(
+                    Arg::new("keep-temp-on-error")
+                        .long("keep-temp-on-error")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Keep the temp dir (SVG, args) of failed renders and log where it is"),
+                )
+                .arg(
+                    Arg::new("access-log")
+                        .long("access-log")
+                        .value_name("BOOL")
+                        .value_parser(clap::value_parser!(bool))
+                        .num_args(0..=1)
+                        .default_value("true")
+                        .default_missing_value("true")
+                        .help("Log each HTTP request (method, path, status, size, duration)"),
+                )
+                .arg(
+                    Arg::new("max-body-bytes")
+                        .long("max-body-bytes")
+                        .value_name("BYTES")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("16777216")
+                        .help("Reject request bodies larger than BYTES with 413 (default 16 MiB)"),
                 )
+                .arg(
+                    Arg::new("max-svg-bytes")
+                        .long("max-svg-bytes")
+                        .value_name("BYTES")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("8388608")
+                        .help("Reject an SVG field larger than BYTES with 413 (default 8 MiB; must be below --max-body-bytes)"),
+                )
+                .arg(
+                    Arg::new("max-svg-elements")
+                        .long("max-svg-elements")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50000")
+                        .help("Reject uploaded SVGs with more than N shape elements (path, polygon, ...)"),
+                )
+                .arg(
+                    Arg::new("default-svg")
+                        .long("default-svg")
+                        .value_name("FILE")
+                        .help("SVG used by GET /render and by POST /render when no file is uploaded"),
+                )
+                .arg(
+                    Arg::new("openscad-bin")
+                        .long("openscad-bin")
+                        .value_name("PATH")
+                        .default_value("openscad")
+                        .help("OpenSCAD executable to run"),
+                )
+                .arg(
+                    Arg::new("param-set")
+                        .long("param-set")
+                        .value_name("FILE")
+                        .help("OpenSCAD Customizer parameter-set JSON to use as default values"),
+                )
+                .arg(
+                    Arg::new("expose")
+                        .long("expose")
+                        .value_name("NAMES")
+                        .value_delimiter(',')
+                        .action(clap::ArgAction::Append)
+                        .help("Comma-separated SCAD params to show in the form, overriding // @param markers"),
+                )
+                .arg(
+                    Arg::new("reject-unknown-params")
+                        .long("reject-unknown-params")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Answer 400 for form fields that match no template param instead of ignoring them"),
+                )
+                .arg(
+                    Arg::new("expose-file")
+                        .long("expose-file")
+                        .value_name("PATH")
+                        .help("File listing params to show in the form (one per line or comma separated)"),
+                )
+                .arg(
+                    Arg::new("param-set-name")
+                        .long("param-set-name")
+                        .value_name("NAME")
+                        .requires("param-set")
+                        .help("Which set to load from --param-set (needed if it holds several)"),
+                ),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Render a batch of parts from a JSON job list")
+                .arg(
+                    Arg::new("input-scad")
+                        .long("input-scad")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Path to input.scad template file"),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("JSON list of jobs: {\"svg\", \"name\", \"params\"}"),
+                )
+                .arg(
+                    Arg::new("out-dir")
+                        .long("out-dir")
+                        .value_name("DIR")
+                        .default_value(".")
+                        .help("Directory to write <name>.stl files into"),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .long("concurrency")
+                        .value_name("N")
+                        .default_value("1")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help("Maximum number of OpenSCAD processes to run at once"),
+                )
+                .arg(
+                    Arg::new("openscad-bin")
+                        .long("openscad-bin")
+                        .value_name("PATH")
+                        .default_value("openscad")
+                        .help("OpenSCAD executable to run"),
+                )
+                .arg(
+                    Arg::new("scad-lib-path")
+                        .long("scad-lib-path")
+                        .value_name("DIR")
+                        .action(clap::ArgAction::Append)
+                        .help("Library directory for include <...> / use <...>, added to OpenSCADPATH (repeatable)"),
+                ),
+        )
+        .subcommand(
+            Command::new("render")
+                .about("Render one part to an STL file, or to stdout with -o -")
+                .arg(
+                    Arg::new("input-scad")
+                        .long("input-scad")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Path to input.scad template file"),
+                )
+                .arg(
+                    Arg::new("svg")
+                        .long("svg")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("SVG passed to the template as SVG_PATH"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .value_name("NAME")
+                        .help("Part name, passed as NAME (default: the SVG file stem)"),
+                )
+                .arg(
+                    Arg::new("openscad-bin")
+                        .long("openscad-bin")
+                        .value_name("PATH")
+                        .default_value("openscad")
+                        .help("OpenSCAD executable to run"),
+                )
+                .arg(
+                    Arg::new("scad-lib-path")
+                        .long("scad-lib-path")
+                        .value_name("DIR")
+                        .action(clap::ArgAction::Append)
+                        .help("Library directory for include <...> / use <...>, added to OpenSCADPATH (repeatable)"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the STL; - for stdout (default: <name>.stl)"),
+                ),
+        )
+        .subcommand(
+            Command::new("form")
+                .about("Write the form page to a file, for hosting apart from the server")
                 .arg(
                     Arg::new("input-scad")
                         .long("input-scad")
                         .value_name("PATH")
                         .required(true)
                         .help("Path to input.scad template file"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Where to write the HTML"),
+                )
+                .arg(
+                    Arg::new("api-url")
+                        .long("api-url")
+                        .value_name("URL")
+                        .help("Server the form posts to and loads its assets from, e.g. https://parts.example.com (default: same origin)"),
+                )
+                .arg(
+                    Arg::new("title")
+                        .long("title")
+                        .value_name("TEXT")
+                        .help("Form page <title> and heading"),
+                )
+                .arg(
+                    Arg::new("subtitle")
+                        .long("subtitle")
+                        .value_name("TEXT")
+                        .help("Text under the form page heading"),
+                )
+                .arg(
+                    Arg::new("theme")
+                        .long("theme")
+                        .value_name("THEME")
+                        .value_parser(["auto", "dark", "light"])
+                        .default_value("auto")
+                        .help("Form page color scheme; auto follows the visitor's OS setting"),
+                )
+                .arg(
+                    Arg::new("param-prefix")
+                        .long("param-prefix")
+                        .value_name("PREFIX")
+                        .default_value("")
+                        .help("Prefix for template param field names; match the server's --param-prefix"),
+                )
+                .arg(
+                    Arg::new("param-set")
+                        .long("param-set")
+                        .value_name("FILE")
+                        .help("OpenSCAD Customizer parameter-set JSON to use as default values"),
+                )
+                .arg(
+                    Arg::new("param-set-name")
+                        .long("param-set-name")
+                        .value_name("NAME")
+                        .requires("param-set")
+                        .help("Which set to load from --param-set (needed if it holds several)"),
+                )
+                .arg(
+                    Arg::new("expose")
+                        .long("expose")
+                        .value_name("NAMES")
+                        .value_delimiter(',')
+                        .action(clap::ArgAction::Append)
+                        .help("Comma-separated SCAD params to show in the form, overriding // @param markers"),
+                )
+                .arg(
+                    Arg::new("expose-file")
+                        .long("expose-file")
+                        .value_name("PATH")
+                        .help("File listing params to show in the form (one per line or comma separated)"),
+                )
+                .arg(
+                    Arg::new("scad-lib-path")
+                        .long("scad-lib-path")
+                        .value_name("DIR")
+                        .action(clap::ArgAction::Append)
+                        .help("Library directory for include <...> / use <...>, added to OpenSCADPATH (repeatable)"),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Report template params missing @param markers or options")
+                .arg(
+                    Arg::new("input-scad")
+                        .long("input-scad")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Path to the .scad template to check"),
+                )
+                .arg(
+                    Arg::new("warn-only")
+                        .long("warn-only")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Exit 0 even when there are findings"),
                 ),
         )
 }
@@ -0,0 +1,176 @@
+//! Rendered files held for a follow-up `GET /download/{token}`.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How long a stashed render stays fetchable.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Most renders held at once; the oldest is dropped to make room.
+pub const DEFAULT_MAX_ENTRIES: usize = 64;
+
+/// One stashed render.
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub bytes: bytes::Bytes,
+    pub content_type: String,
+    pub file_name: String,
+}
+
+#[derive(Debug)]
+struct Entry {
+    download: Download,
+    stored_at: Instant,
+}
+
+/// In-memory token -> render map. Each download can be fetched once, until
+/// it expires. Expired entries are dropped lazily on insert and fetch.
+#[derive(Debug)]
+pub struct DownloadStore {
+    entries: Mutex<HashMap<String, Entry>>,
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl Default for DownloadStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL, DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl DownloadStore {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::default(),
+            ttl,
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    /// Stash `download` and return the token that fetches it.
+    pub fn insert(&self, download: Download) -> String {
+        self.insert_at(download, Instant::now())
+    }
+
+    /// Remove and return the download for `token`, unless it has expired.
+    pub fn take(&self, token: &str) -> Option<Download> {
+        self.take_at(token, Instant::now())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert_at(&self, download: Download, now: Instant) -> String {
+        let token = new_token();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, e| now.duration_since(e.stored_at) < self.ttl);
+        while entries.len() >= self.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, e)| e.stored_at)
+                .map(|(k, _)| k.clone());
+            match oldest {
+                Some(key) => entries.remove(&key),
+                None => break,
+            };
+        }
+        entries.insert(
+            token.clone(),
+            Entry {
+                download,
+                stored_at: now,
+            },
+        );
+        token
+    }
+
+    fn take_at(&self, token: &str, now: Instant) -> Option<Download> {
+        let entry = self.entries.lock().unwrap().remove(token)?;
+        (now.duration_since(entry.stored_at) < self.ttl).then_some(entry.download)
+    }
+}
+
+/// 128-bit hex token drawn from the OS's CSPRNG, so tokens can't be
+/// guessed from earlier ones.
+pub(crate) fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("OS randomness is unavailable");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn download(body: &'static [u8]) -> Download {
+        Download {
+            bytes: bytes::Bytes::from_static(body),
+            content_type: "model/stl".to_string(),
+            file_name: "part.stl".to_string(),
+        }
+    }
+
+    #[test]
+    fn insert_then_take_once() {
+        let store = DownloadStore::default();
+        let token = store.insert(download(b"solid"));
+        assert_eq!(token.len(), 32);
+        assert_eq!(store.take(&token).unwrap().bytes.as_ref(), b"solid");
+        assert!(store.take(&token).is_none());
+    }
+
+    #[test]
+    fn tokens_are_unique() {
+        let store = DownloadStore::default();
+        let a = store.insert(download(b"a"));
+        let b = store.insert(download(b"b"));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn expired_downloads_are_gone() {
+        let store = DownloadStore::new(Duration::from_secs(60), 8);
+        let start = Instant::now();
+        let fresh = store.insert_at(download(b"fresh"), start);
+        let stale = store.insert_at(download(b"stale"), start);
+
+        let later = start + Duration::from_secs(30);
+        assert!(store.take_at(&fresh, later).is_some());
+
+        let expired = start + Duration::from_secs(61);
+        assert!(store.take_at(&stale, expired).is_none());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn insert_sweeps_expired_entries() {
+        let store = DownloadStore::new(Duration::from_secs(60), 8);
+        let start = Instant::now();
+        store.insert_at(download(b"old"), start);
+        store.insert_at(download(b"new"), start + Duration::from_secs(120));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn full_store_evicts_oldest() {
+        let store = DownloadStore::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        let first = store.insert_at(download(b"1"), start);
+        let second = store.insert_at(download(b"2"), start + Duration::from_secs(1));
+        let third = store.insert_at(download(b"3"), start + Duration::from_secs(2));
+
+        let now = start + Duration::from_secs(3);
+        assert_eq!(store.len(), 2);
+        assert!(store.take_at(&first, now).is_none());
+        assert!(store.take_at(&second, now).is_some());
+        assert!(store.take_at(&third, now).is_some());
+    }
+}
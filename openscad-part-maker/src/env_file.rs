@@ -0,0 +1,130 @@
+//! `KEY=VALUE` files for `--env-file`, applied to the OpenSCAD child only.
+//!
+//! The syntax is the common dotenv subset: blank lines and `#` comments are
+//! skipped, an optional `export ` prefix is allowed, double-quoted values
+//! take `\"`, `\\`, `\n` and `\t` escapes, single-quoted values are literal,
+//! and unquoted values end at a ` #` comment.
+
+use std::path::Path;
+
+use anyhow::{Context, bail};
+
+/// Read and parse the env file at `path`.
+pub fn load(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("read env file {}", path.display()))?;
+    parse(&text).with_context(|| format!("parse env file {}", path.display()))
+}
+
+/// Parse env file text into `(key, value)` pairs, in file order.
+pub fn parse(text: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    for (idx, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("line {}: expected KEY=VALUE", idx + 1);
+        };
+        let key = key.trim();
+        if !is_valid_key(key) {
+            bail!("line {}: invalid variable name {key:?}", idx + 1);
+        }
+        let value = parse_value(value.trim()).with_context(|| format!("line {}", idx + 1))?;
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_value(value: &str) -> anyhow::Result<String> {
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return check_trailing(chars.as_str()).map(|()| out),
+                '\\' => match chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(other) => out.push(other),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        bail!("unterminated double quote");
+    }
+    if let Some(rest) = value.strip_prefix('\'') {
+        let Some((inner, trailing)) = rest.split_once('\'') else {
+            bail!("unterminated single quote");
+        };
+        check_trailing(trailing)?;
+        return Ok(inner.to_string());
+    }
+    let value = match value.find(" #") {
+        Some(idx) => &value[..idx],
+        None => value,
+    };
+    Ok(value.trim_end().to_string())
+}
+
+/// Only whitespace or a comment may follow a closing quote.
+fn check_trailing(rest: &str) -> anyhow::Result<()> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        bail!("unexpected text after closing quote: {rest:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_comments_exports_and_quotes() {
+        let text = r#"
+# OpenSCAD library search path
+OPENSCADPATH=/opt/scad/libs:/usr/share/openscad
+export OPENSCAD_THREADS = 4   # per render
+GREETING="hello \"world\"\tand\\more" # trailing comment
+LITERAL='no $escapes \n here'
+EMPTY=
+HASH=a#b
+"#;
+        let vars = parse(text).unwrap();
+        let get = |k: &str| vars.iter().find(|(key, _)| key == k).unwrap().1.as_str();
+        assert_eq!(vars.len(), 6);
+        assert_eq!(get("OPENSCADPATH"), "/opt/scad/libs:/usr/share/openscad");
+        assert_eq!(get("OPENSCAD_THREADS"), "4");
+        assert_eq!(get("GREETING"), "hello \"world\"\tand\\more");
+        assert_eq!(get("LITERAL"), "no $escapes \\n here");
+        assert_eq!(get("EMPTY"), "");
+        assert_eq!(get("HASH"), "a#b");
+    }
+
+    #[test]
+    fn rejects_malformed_lines_with_line_numbers() {
+        for (text, expected) in [
+            ("OK=1\nno equals sign\n", "line 2: expected KEY=VALUE"),
+            ("1BAD=x\n", "line 1: invalid variable name"),
+            ("A=\"open\n", "unterminated double quote"),
+            ("A='open\n", "unterminated single quote"),
+            ("A=\"x\" y\n", "unexpected text after closing quote"),
+        ] {
+            let err = format!("{:#}", parse(text).unwrap_err());
+            assert!(err.contains(expected), "{text:?}: {err}");
+        }
+    }
+}
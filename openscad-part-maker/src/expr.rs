@@ -0,0 +1,161 @@
+//! Arithmetic over OpenSCAD default expressions such as `COASTER_D/2`, so
+//! derived params can show the value they work out to.
+//!
+//! Only numbers, variable references, `+ - * /`, unary minus and
+//! parentheses are understood; anything else (function calls, vectors,
+//! ternaries) makes the whole expression unknown. The result is for display:
+//! renders still hand OpenSCAD the original expression.
+
+/// Evaluate `expr`, resolving variables with `lookup`. `None` if the
+/// expression is outside the supported subset, names an unknown variable
+/// or doesn't come out finite (e.g. division by zero).
+pub fn eval(expr: &str, lookup: impl Fn(&str) -> Option<f64>) -> Option<f64> {
+    let mut parser = Parser {
+        rest: expr,
+        lookup: &lookup,
+    };
+    let value = parser.sum()?;
+    parser.skip_space();
+    (parser.rest.is_empty() && value.is_finite()).then_some(value)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    lookup: &'a dyn Fn(&str) -> Option<f64>,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// Consume `op` if it comes next.
+    fn eat(&mut self, op: char) -> bool {
+        self.skip_space();
+        match self.rest.strip_prefix(op) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn sum(&mut self) -> Option<f64> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                value /= self.unary()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<f64> {
+        if self.eat('-') {
+            return Some(-self.unary()?);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        if self.eat('(') {
+            let value = self.sum()?;
+            return self.eat(')').then_some(value);
+        }
+        self.skip_space();
+        let first = self.rest.chars().next()?;
+        if first.is_ascii_digit() || first == '.' {
+            self.number()
+        } else if first.is_ascii_alphabetic() || first == '_' || first == '$' {
+            let len = self
+                .rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(self.rest.len());
+            let (name, rest) = self.rest.split_at(len);
+            self.rest = rest;
+            (self.lookup)(name)
+        } else {
+            None
+        }
+    }
+
+    /// A literal like `12`, `101.6` or `1e3`.
+    fn number(&mut self) -> Option<f64> {
+        let bytes = self.rest.as_bytes();
+        let mut len = 0;
+        while len < bytes.len() && (bytes[len].is_ascii_digit() || bytes[len] == b'.') {
+            len += 1;
+        }
+        if len < bytes.len() && matches!(bytes[len], b'e' | b'E') {
+            let mut end = len + 1;
+            if end < bytes.len() && matches!(bytes[end], b'+' | b'-') {
+                end += 1;
+            }
+            if end < bytes.len() && bytes[end].is_ascii_digit() {
+                while end < bytes.len() && bytes[end].is_ascii_digit() {
+                    end += 1;
+                }
+                len = end;
+            }
+        }
+        let (literal, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        literal.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_vars(_: &str) -> Option<f64> {
+        None
+    }
+
+    #[test]
+    fn arithmetic_follows_precedence() {
+        assert_eq!(eval("101.6/2", no_vars), Some(50.8));
+        assert_eq!(eval("1 + 2 * 3", no_vars), Some(7.0));
+        assert_eq!(eval("(1 + 2) * 3", no_vars), Some(9.0));
+        assert_eq!(eval("-4 - -2", no_vars), Some(-2.0));
+        assert_eq!(eval("1e3 / 8", no_vars), Some(125.0));
+    }
+
+    #[test]
+    fn variables_come_from_lookup() {
+        let lookup = |name: &str| (name == "COASTER_D").then_some(101.6);
+        assert_eq!(eval("COASTER_D/2 + 1", lookup), Some(51.8));
+        assert_eq!(eval("OTHER/2", lookup), None);
+    }
+
+    #[test]
+    fn unsupported_expressions_are_unknown() {
+        for expr in [
+            "sqrt(2)",
+            "[1, 2]",
+            "a ? 1 : 2",
+            "1 +",
+            "(1",
+            "1 / 0",
+            "\"x\"",
+        ] {
+            assert_eq!(eval(expr, no_vars), None, "{expr}");
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! `--filename-template`: download names built from the params, the export
+//! format and the date, e.g. `{name}-{mode}-{date}.{ext}`.
+//!
+//! Placeholders are `{name}` (the NAME param, or `output`), `{ext}`,
+//! `{date}` (UTC, `YYYY-MM-DD`) and any template param by its lowercase
+//! name. The filled-in result is sanitized into one safe file name.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::bail;
+
+use crate::format::ExportFormat;
+use crate::scad_params::{ScadParamTemplate, ScadParams};
+
+/// Placeholders that don't name a template param.
+const BUILTINS: &[&str] = &["name", "ext", "date"];
+
+/// Check `pattern` once at startup: braces must pair up and every
+/// placeholder must be a builtin or one of `template`'s params.
+pub fn check(pattern: &str, template: &ScadParamTemplate) -> anyhow::Result<()> {
+    let mut unknown = Vec::new();
+    expand(pattern, |key| {
+        if !BUILTINS.contains(&key) && !template.specs.contains_key(&key.to_ascii_uppercase()) {
+            unknown.push(key.to_string());
+        }
+        String::new()
+    })?;
+    if !unknown.is_empty() {
+        bail!("unknown placeholder(s): {}", unknown.join(", "));
+    }
+    Ok(())
+}
+
+/// Fill in `pattern` for one render (see `check`) and sanitize the result.
+pub fn render(pattern: &str, params: &ScadParams, format: ExportFormat, date: &str) -> String {
+    let value = |scad_name: &str| {
+        params
+            .get_raw(scad_name)
+            .map(|raw| raw.trim().trim_matches('"').to_string())
+    };
+    let filled = expand(pattern, |key| match key {
+        "name" => value("NAME").unwrap_or_else(|| "output".into()),
+        "ext" => format.extension().into(),
+        "date" => date.into(),
+        _ => value(&key.to_ascii_uppercase()).unwrap_or_default(),
+    })
+    .unwrap_or_else(|_| pattern.to_string());
+    sanitize(&filled, format)
+}
+
+/// Replace each `{key}` in `pattern` with `lookup(key)`.
+fn expand(pattern: &str, mut lookup: impl FnMut(&str) -> String) -> anyhow::Result<String> {
+    let mut out = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            bail!("unmatched '}}' in {pattern:?}");
+        }
+        let Some(len) = rest[start + 1..].find('}') else {
+            bail!("unterminated '{{' in {pattern:?}");
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&lookup(&rest[start + 1..start + 1 + len]));
+        rest = &rest[start + 2 + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Keep ASCII letters, digits, `-`, `_` and `.`; anything else (including
+/// path separators) becomes `_`. Leading dots are dropped so the name can't
+/// be hidden or relative, and an empty result falls back to `output.<ext>`.
+fn sanitize(name: &str, format: ExportFormat) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.');
+    if cleaned.is_empty() {
+        format!("output.{}", format.extension())
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// `now` as a UTC calendar date, `YYYY-MM-DD`.
+pub fn utc_date(now: SystemTime) -> String {
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn template() -> ScadParamTemplate {
+        ScadParamTemplate::from_scad_text(
+            "NAME = \"coaster\"; // @param\nMODE = \"base\"; // @param options: base|inlay\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn placeholders_are_filled_from_params_format_and_date() {
+        let params = template().instantiate();
+        let pattern = "{name}-{mode}-{date}.{ext}";
+        assert_eq!(
+            render(pattern, &params, ExportFormat::Dxf, "2024-03-01"),
+            "coaster-base-2024-03-01.dxf"
+        );
+        assert!(check(pattern, &template()).is_ok());
+    }
+
+    #[test]
+    fn rendered_names_are_sanitized() {
+        let mut params = template().instantiate();
+        params.set_string("NAME", "../My Logo/v2");
+        assert_eq!(
+            render("{name}.{ext}", &params, ExportFormat::Stl, "2024-03-01"),
+            "_My_Logo_v2.stl"
+        );
+        params.set_string("NAME", "");
+        assert_eq!(
+            render("..{name}", &params, ExportFormat::Stl, "2024-03-01"),
+            "output.stl"
+        );
+    }
+
+    #[test]
+    fn check_rejects_unknown_and_unbalanced_placeholders() {
+        let err = check("{name}-{color}.{ext}", &template()).unwrap_err();
+        assert_eq!(err.to_string(), "unknown placeholder(s): color");
+        assert!(check("{name.{ext}", &template()).is_err());
+        assert!(check("name}.stl", &template()).is_err());
+    }
+
+    #[test]
+    fn utc_date_formats_calendar_day() {
+        assert_eq!(utc_date(UNIX_EPOCH), "1970-01-01");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1_709_164_800 + 3_600);
+        assert_eq!(utc_date(leap_day), "2024-02-29");
+    }
+}
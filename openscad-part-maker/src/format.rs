@@ -0,0 +1,254 @@
+//! Export formats supported by the render endpoint.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Stl,
+    Dxf,
+    Svg,
+    /// A rendered image of the part, for previews.
+    Png,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [
+        ExportFormat::Stl,
+        ExportFormat::Dxf,
+        ExportFormat::Svg,
+        ExportFormat::Png,
+    ];
+
+    /// File extension OpenSCAD uses to pick the exporter (`-o out.<ext>`).
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Stl => "stl",
+            ExportFormat::Dxf => "dxf",
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+        }
+    }
+
+    /// Built-in `Content-Type`; servers go through `FormatMap` instead so
+    /// `--format-map` can override it.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Stl => "model/stl",
+            ExportFormat::Dxf => "image/vnd.dxf",
+            ExportFormat::Svg => "image/svg+xml",
+            ExportFormat::Png => "image/png",
+        }
+    }
+
+    /// 2D exporters fail on 3D geometry (and vice versa).
+    pub fn is_2d(self) -> bool {
+        matches!(self, ExportFormat::Dxf | ExportFormat::Svg)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "stl" => Ok(ExportFormat::Stl),
+            "dxf" => Ok(ExportFormat::Dxf),
+            "svg" => Ok(ExportFormat::Svg),
+            "png" => Ok(ExportFormat::Png),
+            other => Err(format!("unsupported format '{other}'")),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// `Content-Type` for each export format's downloads, keyed by extension:
+/// the built-in types, with `--format-map EXT=MIME` entries replacing them.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(transparent)]
+pub struct FormatMap(BTreeMap<String, String>);
+
+impl Default for FormatMap {
+    fn default() -> Self {
+        Self(
+            ExportFormat::ALL
+                .iter()
+                .map(|format| (format.extension().into(), format.content_type().into()))
+                .collect(),
+        )
+    }
+}
+
+impl FormatMap {
+    pub fn content_type(&self, format: ExportFormat) -> &str {
+        self.0
+            .get(format.extension())
+            .map_or(format.content_type(), String::as_str)
+    }
+
+    /// Apply one `EXT=MIME` entry. The extension must be a supported format,
+    /// and the type must look like `type/subtype` (parameters allowed).
+    pub fn insert(&mut self, entry: &str) -> Result<(), String> {
+        let (ext, mime) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("expected EXT=MIME, got '{entry}'"))?;
+        let format: ExportFormat = ext.parse()?;
+        let mime = mime.trim();
+        let essence = mime.split(';').next().unwrap_or_default().trim();
+        let well_formed = matches!(
+            essence.split_once('/'),
+            Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty()
+        ) && !essence.contains(char::is_whitespace)
+            && mime.bytes().all(|b| b == b' ' || b.is_ascii_graphic());
+        if !well_formed {
+            return Err(format!("invalid content type '{mime}' for '{format}'"));
+        }
+        self.0.insert(format.extension().into(), mime.into());
+        Ok(())
+    }
+}
+
+/// Color schemes OpenSCAD's `--colorscheme` accepts for PNG export.
+pub const COLORSCHEMES: &[&str] = &[
+    "Cornfield",
+    "Metallic",
+    "Sunset",
+    "Starnight",
+    "BeforeDawn",
+    "Nature",
+    "DeepOcean",
+    "Solarized",
+    "Tomorrow",
+    "Tomorrow Night",
+    "Monotone",
+];
+
+/// The `COLORSCHEMES` entry matching `name`, ignoring case.
+pub fn parse_colorscheme(name: &str) -> Result<&'static str, String> {
+    let name = name.trim();
+    COLORSCHEMES
+        .iter()
+        .find(|scheme| scheme.eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| {
+            format!(
+                "unknown colorscheme '{name}' (expected one of: {})",
+                COLORSCHEMES.join(", ")
+            )
+        })
+}
+
+/// Check `format` against the template's `// @mode-formats` declarations.
+///
+/// Templates without declarations are not checked. Once any mode is declared,
+/// modes that aren't listed are treated as 3D-only.
+pub fn check_mode_format(
+    mode_formats: &BTreeMap<String, Vec<ExportFormat>>,
+    mode: Option<&str>,
+    format: ExportFormat,
+) -> Result<(), String> {
+    if mode_formats.is_empty() {
+        return Ok(());
+    }
+    let mode = mode.unwrap_or("");
+    let allowed = match mode_formats.get(mode) {
+        Some(formats) => formats.contains(&format),
+        None => !format.is_2d(),
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(format!(
+            "format '{format}' is not supported for mode '{mode}'"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_and_content_type_mapping() {
+        let cases = [
+            ("stl", "stl", "model/stl", false),
+            ("dxf", "dxf", "image/vnd.dxf", true),
+            ("SVG", "svg", "image/svg+xml", true),
+            ("png", "png", "image/png", false),
+        ];
+        for (input, ext, ctype, is_2d) in cases {
+            let f: ExportFormat = input.parse().unwrap();
+            assert_eq!(f.extension(), ext);
+            assert_eq!(f.content_type(), ctype);
+            assert_eq!(f.is_2d(), is_2d);
+        }
+        assert!("obj".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn format_map_defaults_to_builtin_content_types() {
+        let map = FormatMap::default();
+        for format in ExportFormat::ALL {
+            assert_eq!(map.content_type(format), format.content_type());
+        }
+        assert_eq!(map.content_type(ExportFormat::Stl), "model/stl");
+    }
+
+    #[test]
+    fn format_map_entries_override_one_format() {
+        let mut map = FormatMap::default();
+        map.insert("STL=application/sla").unwrap();
+        map.insert("dxf = application/dxf; charset=us-ascii")
+            .unwrap();
+        assert_eq!(map.content_type(ExportFormat::Stl), "application/sla");
+        assert_eq!(
+            map.content_type(ExportFormat::Dxf),
+            "application/dxf; charset=us-ascii"
+        );
+        assert_eq!(map.content_type(ExportFormat::Svg), "image/svg+xml");
+
+        assert!(map.insert("application/sla").is_err());
+        assert!(map.insert("obj=model/obj").is_err());
+        for bad in [
+            "stl=",
+            "stl=model",
+            "stl=/stl",
+            "stl=model/st l",
+            "stl=model/stl; x=\u{1}",
+        ] {
+            let err = map.insert(bad).unwrap_err();
+            assert!(err.contains("invalid content type"), "{bad}: {err}");
+        }
+    }
+
+    #[test]
+    fn colorschemes_match_case_insensitively() {
+        assert_eq!(parse_colorscheme("sunset"), Ok("Sunset"));
+        assert_eq!(parse_colorscheme(" tomorrow night "), Ok("Tomorrow Night"));
+        let err = parse_colorscheme("Neon").unwrap_err();
+        assert!(err.contains("Neon") && err.contains("Cornfield"));
+    }
+
+    #[test]
+    fn mode_format_compatibility() {
+        let mut map = BTreeMap::new();
+        assert!(check_mode_format(&map, Some("base"), ExportFormat::Dxf).is_ok());
+
+        map.insert(
+            "cut".to_string(),
+            vec![ExportFormat::Dxf, ExportFormat::Svg],
+        );
+        assert!(check_mode_format(&map, Some("cut"), ExportFormat::Dxf).is_ok());
+        assert!(check_mode_format(&map, Some("cut"), ExportFormat::Stl).is_err());
+        assert!(check_mode_format(&map, Some("base"), ExportFormat::Stl).is_ok());
+        let err = check_mode_format(&map, Some("base"), ExportFormat::Svg).unwrap_err();
+        assert!(err.contains("base"));
+    }
+}
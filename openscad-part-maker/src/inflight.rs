@@ -0,0 +1,118 @@
+//! Deduplication of concurrent work by key (see `Idempotency-Key`).
+//!
+//! The first caller for a key runs its future; callers arriving while it is
+//! in flight wait for and share its result. Keys are forgotten once the work
+//! finishes, so only overlapping requests are merged, never later retries.
+//! Each run carries a fingerprint of its input, so a key reused for
+//! different work is refused rather than handed someone else's result.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+
+/// A run's input fingerprint and the cell its result lands in.
+type Cell<T> = (u64, Arc<OnceCell<T>>);
+
+pub struct InFlight<T> {
+    cells: Mutex<HashMap<String, Cell<T>>>,
+}
+
+/// A key already in flight for work with a different fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyReused;
+
+impl<T> Default for InFlight<T> {
+    fn default() -> Self {
+        Self {
+            cells: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone> InFlight<T> {
+    /// Run `work` for `key` unless a run for it is already in flight, in
+    /// which case wait for that one's result. If the running caller is
+    /// dropped mid-way, a waiting caller takes over with its own `work`.
+    /// `fingerprint` identifies the input of `work`; a run in flight for the
+    /// same key with another fingerprint makes this a `KeyReused` error.
+    pub async fn run<F, Fut>(&self, key: &str, fingerprint: u64, work: F) -> Result<T, KeyReused>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let cell = {
+            let mut cells = self.cells.lock().unwrap();
+            let (running, cell) = cells
+                .entry(key.to_string())
+                .or_insert_with(|| (fingerprint, Arc::default()));
+            if *running != fingerprint {
+                return Err(KeyReused);
+            }
+            cell.clone()
+        };
+        let value = cell.get_or_init(work).await.clone();
+
+        let mut cells = self.cells.lock().unwrap();
+        if cells.get(key).is_some_and(|(_, c)| Arc::ptr_eq(c, &cell)) {
+            cells.remove(key);
+        }
+        Ok(value)
+    }
+
+    /// Number of keys currently in flight.
+    pub fn len(&self) -> usize {
+        self.cells.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn concurrent_runs_share_one_result() {
+        let in_flight = InFlight::<usize>::default();
+        let runs = AtomicUsize::new(0);
+        let work = || async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            runs.fetch_add(1, Ordering::SeqCst) + 1
+        };
+
+        let (a, b) = tokio::join!(in_flight.run("k", 7, work), in_flight.run("k", 7, work));
+        assert_eq!((a, b), (Ok(1), Ok(1)));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(in_flight.is_empty(), "finished keys are forgotten");
+
+        // A later call with the same key runs again.
+        assert_eq!(in_flight.run("k", 7, work).await, Ok(2));
+        // Different keys don't wait on each other.
+        let (c, d) = tokio::join!(in_flight.run("x", 7, work), in_flight.run("y", 7, work));
+        assert_eq!(c.unwrap() + d.unwrap(), 3 + 4);
+    }
+
+    #[tokio::test]
+    async fn reused_key_with_other_input_is_refused() {
+        let in_flight = InFlight::<usize>::default();
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            1
+        };
+        let (a, b) = tokio::join!(in_flight.run("k", 1, slow), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            in_flight.run("k", 2, || async { 2 }).await
+        });
+        assert_eq!(a, Ok(1));
+        assert_eq!(b, Err(KeyReused));
+        // Once the first run is done, the key is free for other input.
+        assert_eq!(in_flight.run("k", 2, || async { 2 }).await, Ok(2));
+    }
+}
@@ -0,0 +1,30 @@
+//! Render OpenSCAD parts from an uploaded SVG and a parameterized template.
+//!
+//! The `openscad-part-maker` binary is a thin CLI over this crate: `server`
+//! serves the web form and `batch` renders job files, both through
+//! [`Renderer`].
+
+pub mod batch;
+pub mod cache;
+pub mod downloads;
+pub mod env_file;
+pub mod expr;
+pub mod filename;
+pub mod format;
+pub mod inflight;
+pub mod lint;
+pub mod openapi;
+#[doc(hidden)]
+pub mod prelude;
+pub mod queue;
+pub mod render;
+pub mod scad_params;
+pub mod server;
+pub mod stl;
+pub mod svg;
+pub mod throttle;
+pub mod uploads;
+pub mod webhook;
+
+pub use format::{ExportFormat, FormatMap};
+pub use render::{Camera, PreviewOptions, Quality, RenderError, RenderOptions, Renderer};
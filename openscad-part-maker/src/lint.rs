@@ -0,0 +1,113 @@
+//! `lint` subcommand: spot template params that need annotations.
+//!
+//! Works on the params `extract_param_specs` finds in the main file, so the
+//! findings match what the server would discover.
+
+use std::{io::Write, path::Path};
+
+use anyhow::Context;
+
+use crate::scad_params::{ParamSpec, ParamType, extract_param_specs, is_svg_define};
+
+/// One problem with a param declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub name: String,
+    pub message: &'static str,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+/// Lint the `.scad` source in `text`.
+///
+/// - A plain literal default (number, bool or string) looks user-facing, so
+///   it should say so with `// @param`.
+/// - A string param without `options:` is a free text box; most templates
+///   mean one of a few values and want a dropdown.
+///
+/// `NAME` and the SVG path variables are filled in by the server and skipped.
+pub fn lint_scad(text: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for spec in extract_param_specs(text) {
+        if spec.name == "NAME" || is_svg_define(&spec.name) {
+            continue;
+        }
+        if has_literal_default(&spec) && !spec.comment.contains("@param") {
+            findings.push(Finding {
+                name: spec.name.clone(),
+                message: "literal default but no `// @param` marker",
+            });
+        }
+        if spec.ty == ParamType::String && spec.is_user_param && spec.options.is_empty() {
+            findings.push(Finding {
+                name: spec.name,
+                message: "string param without `options:`; add them for a dropdown",
+            });
+        }
+    }
+    findings
+}
+
+fn has_literal_default(spec: &ParamSpec) -> bool {
+    match spec.ty {
+        ParamType::Bool | ParamType::Integer => true,
+        ParamType::Number => spec.default.trim().parse::<f64>().is_ok(),
+        ParamType::String | ParamType::File => {
+            let d = spec.default.trim();
+            d.len() >= 2 && d.ends_with('"') && !d[1..d.len() - 1].contains('"')
+        }
+    }
+}
+
+/// Lint `path` and print one line per finding to `out`.
+pub fn run(path: &Path, out: &mut dyn Write) -> anyhow::Result<Vec<Finding>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let findings = lint_scad(&text);
+    for finding in &findings {
+        let _ = writeln!(out, "{}: {finding}", path.display());
+    }
+    let _ = writeln!(out, "{} finding(s) in {}", findings.len(), path.display());
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_template_has_no_findings() {
+        let text = r#"
+NAME = "coaster";
+SVG_PATH = "logo.svg";
+COASTER_D = 101.6; // @param
+SHAPE = "octagon"; // @param options: octagon|circle
+HOLLOW = false; // @param advanced
+FIT = COASTER_D / 100;
+"#;
+        assert_eq!(lint_scad(text), Vec::new());
+    }
+
+    #[test]
+    fn reports_unmarked_literals_and_free_text_strings() {
+        let text = r#"
+COASTER_D = 101.6; // @param
+THICKNESS = 3;
+ROUNDED = true;
+LABEL = "hello"; // @param
+FIT = COASTER_D / 100;
+"#;
+        let found: Vec<String> = lint_scad(text).iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            found,
+            [
+                "THICKNESS: literal default but no `// @param` marker",
+                "ROUNDED: literal default but no `// @param` marker",
+                "LABEL: string param without `options:`; add them for a dropdown",
+            ]
+        );
+    }
+}
@@ -1,15 +1,19 @@
 use std::io::Write;
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use anyhow::Context;
 use clap::ArgMatches;
 use clap_complete::shells::Shell;
 
 mod cli;
-mod prelude;
-mod scad_params;
-mod server;
 
-use prelude::*;
+use openscad_part_maker::prelude::*;
+use openscad_part_maker::{
+    ExportFormat, FormatMap, RenderOptions, Renderer, batch, env_file, lint, render, scad_params,
+    server, webhook,
+};
 
 fn main() {
     let cmd = cli::app();
@@ -17,10 +21,25 @@ fn main() {
 
     // Configure logging:
     let log_level = determine_log_level(&matches, std::env::var("RUST_LOG").ok());
-    env_logger::Builder::new()
-        .filter_level(log::LevelFilter::from_str(&log_level).unwrap_or(log::LevelFilter::Info))
-        .format_timestamp(None)
-        .init();
+    let mut logger = env_logger::Builder::new();
+    logger.filter_level(log::LevelFilter::from_str(&log_level).unwrap_or(log::LevelFilter::Info));
+    match determine_log_format(&matches) {
+        LogFormat::Text => {
+            logger.format_timestamp(None);
+        }
+        LogFormat::Json => {
+            logger.format(|buf, record| {
+                let line = serde_json::json!({
+                    "level": record.level().as_str(),
+                    "timestamp": buf.timestamp_millis().to_string(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+                writeln!(buf, "{line}")
+            });
+        }
+    }
+    logger.init();
     debug!("logging initialized.");
 
     let mut stdout = io::stdout();
@@ -28,7 +47,7 @@ fn main() {
     let exit_code = run_once_with_serve(
         cmd,
         matches,
-        |m| run_server_from_matches(m),
+        run_server_from_matches,
         &mut stdout,
         &mut stderr,
     );
@@ -40,35 +59,399 @@ fn generate_completion_script_to(shell: clap_complete::shells::Shell, out: &mut
 }
 
 fn run_server_from_matches(sub_matches: &ArgMatches) -> anyhow::Result<()> {
+    let (addr, tile_scad_path, config) = server_settings_from_matches(sub_matches)?;
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(server::run(addr, tile_scad_path, config))
+}
+
+/// `serve --print-config`: everything `serve` would run with, as JSON.
+/// `env_rust_log` is `RUST_LOG`, shown merged with the log flags.
+fn print_config(
+    matches: &ArgMatches,
+    env_rust_log: Option<String>,
+    stdout: &mut dyn Write,
+) -> anyhow::Result<()> {
+    let (_, sub_matches) = matches.subcommand().expect("serve subcommand");
+    let (addr, input_scad, config) = server_settings_from_matches(sub_matches)?;
+    let resolved = serde_json::json!({
+        "log_level": determine_log_level(matches, env_rust_log),
+        "log_format": match determine_log_format(matches) {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        },
+        "listen": addr.to_string(),
+        "input_scad": input_scad,
+        "server": config,
+    });
+    writeln!(stdout, "{}", serde_json::to_string_pretty(&resolved)?)?;
+    Ok(())
+}
+
+fn server_settings_from_matches(
+    sub_matches: &ArgMatches,
+) -> anyhow::Result<(SocketAddr, PathBuf, server::ServerConfig)> {
     let addr_str = sub_matches
         .get_one::<String>("listen")
         .expect("listen has default");
-    let addr: SocketAddr = addr_str.parse()?;
+    let addr = parse_listen_addr(addr_str, sub_matches.contains_id("port-file"))?;
 
-    let tile_scad_path = sub_matches
+    let tile_scad_path: PathBuf = sub_matches
         .get_one::<String>("input-scad")
         .expect("required")
         .into();
 
+    let config = server::ServerConfig {
+        max_fn: *sub_matches
+            .get_one::<i32>("max-fn")
+            .expect("max-fn has default"),
+        deterministic: sub_matches.get_flag("deterministic"),
+        preflight: sub_matches.get_flag("preflight").then(|| {
+            Duration::from_secs(
+                *sub_matches
+                    .get_one::<u64>("preflight-timeout")
+                    .expect("preflight-timeout has default"),
+            )
+        }),
+        expose_errors: sub_matches.get_flag("expose-errors"),
+        param_set: sub_matches
+            .get_one::<String>("param-set")
+            .map(PathBuf::from),
+        param_set_name: sub_matches.get_one::<String>("param-set-name").cloned(),
+        expose: sub_matches
+            .get_many::<String>("expose")
+            .map(|names| names.cloned().collect())
+            .unwrap_or_default(),
+        reject_unknown_params: sub_matches.get_flag("reject-unknown-params"),
+        render_threads: sub_matches.get_one::<u32>("render-threads").copied(),
+        render_nice: sub_matches.get_one::<i32>("render-nice").copied(),
+        render_mem_limit_mib: sub_matches.get_one::<u64>("render-mem-limit").copied(),
+        read_only_template: sub_matches.get_flag("read-only-template"),
+        allow_svg_dir: sub_matches
+            .get_one::<String>("allow-svg-dir")
+            .map(PathBuf::from),
+        filename_template: sub_matches.get_one::<String>("filename-template").cloned(),
+        format_map: parse_format_map(sub_matches)?,
+        allowed_formats: parse_allowed_formats(sub_matches)?,
+        on_render: sub_matches
+            .get_one::<String>("on-render")
+            .map(|url| webhook::parse_url(url))
+            .transpose()
+            .context("invalid --on-render")?,
+        sandbox_cmd: match sub_matches.get_one::<String>("sandbox-cmd") {
+            Some(cmd) => shell_words::split(cmd).context("invalid --sandbox-cmd")?,
+            None => Vec::new(),
+        },
+        enable_features: parse_enable_features(sub_matches)?,
+        scad_lib_paths: parse_scad_lib_paths(sub_matches)?,
+        openscad_env: match sub_matches.get_one::<String>("env-file") {
+            Some(path) => env_file::load(Path::new(path))?,
+            None => Vec::new(),
+        },
+        expose_file: sub_matches
+            .get_one::<String>("expose-file")
+            .map(PathBuf::from),
+        default_svg: sub_matches
+            .get_one::<String>("default-svg")
+            .map(PathBuf::from),
+        openscad_bin: sub_matches
+            .get_one::<String>("openscad-bin")
+            .map(PathBuf::from)
+            .unwrap_or_default(),
+        max_renders: sub_matches
+            .get_one::<u64>("max-renders")
+            .map(|n| *n as usize),
+        max_render_per_minute: sub_matches.get_one::<u32>("max-render-per-minute").copied(),
+        render_cache: sub_matches
+            .get_one::<u64>("render-cache")
+            .map_or(0, |n| *n as usize),
+        render_timeout: sub_matches
+            .get_one::<u64>("render-timeout")
+            .map(|secs| Duration::from_secs(*secs)),
+        slow_render: Duration::from_millis(
+            *sub_matches
+                .get_one::<u64>("slow-render-ms")
+                .expect("slow-render-ms has default"),
+        ),
+        render_retries: *sub_matches
+            .get_one::<u32>("render-retries")
+            .expect("render-retries has default"),
+        keep_temp_on_error: sub_matches.get_flag("keep-temp-on-error"),
+        access_log: *sub_matches
+            .get_one::<bool>("access-log")
+            .expect("access-log has default"),
+        max_body_bytes: *sub_matches
+            .get_one::<usize>("max-body-bytes")
+            .expect("max-body-bytes has default"),
+        max_svg_bytes: *sub_matches
+            .get_one::<usize>("max-svg-bytes")
+            .expect("max-svg-bytes has default"),
+        max_svg_elements: *sub_matches
+            .get_one::<usize>("max-svg-elements")
+            .expect("max-svg-elements has default"),
+        title: sub_matches.get_one::<String>("title").cloned(),
+        subtitle: sub_matches.get_one::<String>("subtitle").cloned(),
+        theme: sub_matches
+            .get_one::<String>("theme")
+            .expect("theme has default")
+            .parse()
+            .map_err(anyhow::Error::msg)?,
+        coordinate_system: sub_matches
+            .get_one::<String>("coordinate-system")
+            .expect("coordinate-system has default")
+            .parse()
+            .map_err(anyhow::Error::msg)?,
+        base_path: server::normalize_base_path(
+            sub_matches
+                .get_one::<String>("base-path")
+                .map_or("", String::as_str),
+        ),
+        admin_token: sub_matches.get_one::<String>("admin-token").cloned(),
+        port_file: sub_matches
+            .get_one::<String>("port-file")
+            .map(PathBuf::from),
+        tls_cert: sub_matches.get_one::<String>("tls-cert").map(PathBuf::from),
+        tls_key: sub_matches.get_one::<String>("tls-key").map(PathBuf::from),
+        strict: sub_matches.get_flag("strict"),
+        fake_render: sub_matches.get_flag("fake-render"),
+        no_index: sub_matches.get_flag("no-index"),
+        param_prefix: sub_matches
+            .get_one::<String>("param-prefix")
+            .cloned()
+            .unwrap_or_default(),
+    };
+    Ok((addr, tile_scad_path, config))
+}
+
+/// The built-in download content types with each `--format-map` applied.
+fn parse_format_map(sub_matches: &ArgMatches) -> anyhow::Result<FormatMap> {
+    let mut map = FormatMap::default();
+    for entry in sub_matches
+        .get_many::<String>("format-map")
+        .into_iter()
+        .flatten()
+    {
+        map.insert(entry)
+            .map_err(|err| anyhow::anyhow!("invalid --format-map: {err}"))?;
+    }
+    Ok(map)
+}
+
+/// `--allowed-formats`, or every export format if the flag isn't given.
+fn parse_allowed_formats(sub_matches: &ArgMatches) -> anyhow::Result<Vec<ExportFormat>> {
+    let Some(names) = sub_matches.get_many::<String>("allowed-formats") else {
+        return Ok(ExportFormat::ALL.to_vec());
+    };
+    let mut formats = Vec::new();
+    for name in names.filter(|name| !name.trim().is_empty()) {
+        let format = name
+            .parse()
+            .map_err(|err| anyhow::anyhow!("invalid --allowed-formats: {err}"))?;
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    anyhow::ensure!(
+        !formats.is_empty(),
+        "--allowed-formats: name at least one format"
+    );
+    Ok(formats)
+}
+
+fn parse_scad_lib_paths(sub_matches: &ArgMatches) -> anyhow::Result<Vec<PathBuf>> {
+    let paths: Vec<PathBuf> = sub_matches
+        .get_many::<String>("scad-lib-path")
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect();
+    std::env::join_paths(&paths).context("invalid --scad-lib-path")?;
+    for path in paths.iter().filter(|path| !path.is_dir()) {
+        warn!("--scad-lib-path {}: not a directory", path.display());
+    }
+    Ok(paths)
+}
+
+fn parse_enable_features(sub_matches: &ArgMatches) -> anyhow::Result<Vec<String>> {
+    let mut features = Vec::new();
+    for name in sub_matches
+        .get_many::<String>("enable-feature")
+        .into_iter()
+        .flatten()
+    {
+        let known = render::check_feature(name)
+            .map_err(|err| anyhow::anyhow!("invalid --enable-feature: {err}"))?;
+        if !known {
+            warn!(
+                "--enable-feature {name}: not a known OpenSCAD feature; passing it through anyway"
+            );
+        }
+        features.push(name.clone());
+    }
+    Ok(features)
+}
+
+/// Parse `--listen`: a full `HOST:PORT`, or a bare port bound on all interfaces.
+/// Port 0 (an ephemeral port) is only accepted with `--port-file`, since
+/// otherwise nobody learns which port was picked.
+fn parse_listen_addr(value: &str, allow_ephemeral: bool) -> anyhow::Result<SocketAddr> {
+    let addr = match value.parse::<u16>() {
+        Ok(port) => SocketAddr::from(([0, 0, 0, 0], port)),
+        Err(_) => value.parse::<SocketAddr>().with_context(|| {
+            format!("invalid --listen value '{value}': expected HOST:PORT or PORT")
+        })?,
+    };
+    if addr.port() == 0 && !allow_ephemeral {
+        anyhow::bail!(
+            "invalid --listen value '{value}': port 0 binds a random port; choose a port or pass --port-file to record it"
+        );
+    }
+    Ok(addr)
+}
+
+fn run_batch_from_matches(
+    sub_matches: &ArgMatches,
+    stdout: &mut dyn Write,
+) -> anyhow::Result<batch::BatchSummary> {
+    let input_scad: PathBuf = sub_matches
+        .get_one::<String>("input-scad")
+        .expect("required")
+        .into();
+    let jobs: PathBuf = sub_matches
+        .get_one::<String>("jobs")
+        .expect("required")
+        .into();
+    let out_dir: PathBuf = sub_matches
+        .get_one::<String>("out-dir")
+        .expect("out-dir has default")
+        .into();
+    let concurrency = *sub_matches
+        .get_one::<u32>("concurrency")
+        .expect("concurrency has default") as usize;
+    let openscad_bin = Path::new(
+        sub_matches
+            .get_one::<String>("openscad-bin")
+            .expect("openscad-bin has default"),
+    );
+
+    let lib_paths = parse_scad_lib_paths(sub_matches)?;
+
     let rt = tokio::runtime::Runtime::new()?;
-    rt.block_on(server::run(addr, tile_scad_path))
+    let summary = rt.block_on(batch::run(
+        openscad_bin,
+        &lib_paths,
+        &input_scad,
+        &jobs,
+        &out_dir,
+        concurrency,
+    ))?;
+    summary.write_report(stdout);
+    Ok(summary)
+}
+
+fn run_render_from_matches(sub_matches: &ArgMatches, stdout: &mut dyn Write) -> anyhow::Result<()> {
+    let input_scad = Path::new(
+        sub_matches
+            .get_one::<String>("input-scad")
+            .expect("required"),
+    );
+    let job = batch::Job {
+        name: sub_matches.get_one::<String>("name").cloned(),
+        ..batch::Job::new(sub_matches.get_one::<String>("svg").expect("required"))
+    };
+    let openscad_bin = sub_matches
+        .get_one::<String>("openscad-bin")
+        .expect("openscad-bin has default");
+    let output = sub_matches
+        .get_one::<String>("output")
+        .map(|arg| batch::RenderOutput::parse(arg));
+
+    let lib_paths = parse_scad_lib_paths(sub_matches)?;
+
+    let template =
+        scad_params::ScadParamTemplate::from_scad_tree_with_libs(input_scad, &lib_paths)?;
+    let mut options = RenderOptions::new(input_scad);
+    options.lib_paths = lib_paths;
+    let renderer = Renderer::new(openscad_bin, template, options);
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(batch::render_one(&renderer, &job, output, stdout))
+}
+
+/// `form`: write the form page for a template to `--output`.
+fn run_form_from_matches(sub_matches: &ArgMatches) -> anyhow::Result<()> {
+    let input_scad = Path::new(
+        sub_matches
+            .get_one::<String>("input-scad")
+            .expect("required"),
+    );
+    let output = Path::new(sub_matches.get_one::<String>("output").expect("required"));
+    let config = server::ServerConfig {
+        base_path: sub_matches
+            .get_one::<String>("api-url")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_default(),
+        title: sub_matches.get_one::<String>("title").cloned(),
+        subtitle: sub_matches.get_one::<String>("subtitle").cloned(),
+        theme: sub_matches
+            .get_one::<String>("theme")
+            .expect("theme has default")
+            .parse()
+            .map_err(anyhow::Error::msg)?,
+        param_prefix: sub_matches
+            .get_one::<String>("param-prefix")
+            .cloned()
+            .unwrap_or_default(),
+        param_set: sub_matches
+            .get_one::<String>("param-set")
+            .map(PathBuf::from),
+        param_set_name: sub_matches.get_one::<String>("param-set-name").cloned(),
+        expose: sub_matches
+            .get_many::<String>("expose")
+            .map(|names| names.cloned().collect())
+            .unwrap_or_default(),
+        expose_file: sub_matches
+            .get_one::<String>("expose-file")
+            .map(PathBuf::from),
+        scad_lib_paths: parse_scad_lib_paths(sub_matches)?,
+        ..server::ServerConfig::default()
+    };
+    let html = server::render_form_html(input_scad, &config)?;
+    std::fs::write(output, html).with_context(|| format!("write {}", output.display()))
 }
 
 /// Decide the effective log level using the same precedence as main():
 /// 1) --verbose forces debug
 /// 2) --log LEVEL
-/// 3) RUST_LOG env var
-/// 4) fallback "info"
+/// 3) --quiet means warn
+/// 4) RUST_LOG env var
+/// 5) fallback "info"
 fn determine_log_level(matches: &ArgMatches, env_rust_log: Option<String>) -> String {
     let log_level = if matches.get_flag("verbose") {
         Some("debug".to_string())
+    } else if let Some(level) = matches.get_one::<String>("log") {
+        Some(level.clone())
+    } else if matches.get_flag("quiet") {
+        Some("warn".to_string())
     } else {
-        matches.get_one::<String>("log").cloned()
+        None
     };
     let log_level = log_level.or(env_rust_log);
     log_level.unwrap_or_else(|| "info".to_string())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Log line format from --log-format (defaults to text).
+fn determine_log_format(matches: &ArgMatches) -> LogFormat {
+    match matches.get_one::<String>("log-format").map(String::as_str) {
+        Some("json") => LogFormat::Json,
+        _ => LogFormat::Text,
+    }
+}
+
 /// Testable core of main() that doesn't touch global logger or exit().
 /// `serve_fn` is injected so unit tests can cover serve ok/error paths.
 fn run_once_with_serve<F>(
@@ -88,12 +471,22 @@ where
         return 0;
     }
 
-    // Handle the subcommands:
-    let _ = writeln!(stderr, "");
+    // Handle the subcommands (padded with blank lines unless --quiet):
+    let quiet = matches.get_flag("quiet");
+    if !quiet {
+        let _ = writeln!(stderr);
+    }
     let exit_code = match matches.subcommand() {
         Some(("hello", sub_matches)) => {
             let name = sub_matches.get_one::<String>("NAME").unwrap();
-            let _ = writeln!(stdout, "Hello, {name}!");
+            let repeat = *sub_matches.get_one::<u32>("repeat").unwrap();
+            let mut greeting = format!("Hello, {name}!");
+            if sub_matches.get_flag("uppercase") {
+                greeting = greeting.to_uppercase();
+            }
+            for _ in 0..repeat {
+                let _ = writeln!(stdout, "{greeting}");
+            }
             0
         }
         Some(("completions", sub_matches)) => {
@@ -102,6 +495,8 @@ where
                     "bash" => generate_completion_script_to(Shell::Bash, stdout),
                     "zsh" => generate_completion_script_to(Shell::Zsh, stdout),
                     "fish" => generate_completion_script_to(Shell::Fish, stdout),
+                    "powershell" => generate_completion_script_to(Shell::PowerShell, stdout),
+                    "elvish" => generate_completion_script_to(Shell::Elvish, stdout),
                     shell => {
                         let _ = writeln!(stderr, "Unsupported shell: {shell}");
                     }
@@ -113,14 +508,14 @@ where
                     "### Instructions to enable tab completion for {}",
                     env!("CARGO_BIN_NAME")
                 );
-                let _ = writeln!(stderr, "");
+                let _ = writeln!(stderr);
                 let _ = writeln!(stderr, "### Bash (put this in ~/.bashrc:)");
                 let _ = writeln!(
                     stderr,
                     "  source <({} completions bash)",
                     env!("CARGO_BIN_NAME")
                 );
-                let _ = writeln!(stderr, "");
+                let _ = writeln!(stderr);
                 let _ = writeln!(stderr, "### To make an alias (eg. 'h'), add this too:");
                 let _ = writeln!(stderr, "  alias h={}", env!("CARGO_BIN_NAME"));
                 let _ = writeln!(
@@ -128,10 +523,10 @@ where
                     "  complete -F _{} -o bashdefault -o default h",
                     env!("CARGO_BIN_NAME")
                 );
-                let _ = writeln!(stderr, "");
+                let _ = writeln!(stderr);
                 let _ = writeln!(
                     stderr,
-                    "### If you don't use Bash, you can also use Fish or Zsh:"
+                    "### If you don't use Bash, you can also use Fish, Zsh, PowerShell or Elvish:"
                 );
                 let _ = writeln!(stderr, "### Fish (put this in ~/.config/fish/config.fish");
                 let _ = writeln!(
@@ -145,9 +540,65 @@ where
                     "  autoload -U compinit; compinit; source <({} completions zsh)",
                     env!("CARGO_BIN_NAME")
                 );
+                let _ = writeln!(stderr, "### PowerShell (put this in your $PROFILE)");
+                let _ = writeln!(
+                    stderr,
+                    "  {} completions powershell | Out-String | Invoke-Expression",
+                    env!("CARGO_BIN_NAME")
+                );
+                let _ = writeln!(stderr, "### Elvish (put this in ~/.config/elvish/rc.elv)");
+                let _ = writeln!(
+                    stderr,
+                    "  eval ({} completions elvish | slurp)",
+                    env!("CARGO_BIN_NAME")
+                );
                 1
             }
         }
+        Some(("batch", sub_matches)) => match run_batch_from_matches(sub_matches, stdout) {
+            Ok(summary) if summary.failed.is_empty() => 0,
+            Ok(_) => 1,
+            Err(err) => {
+                let _ = writeln!(stderr, "Batch error: {err:?}");
+                1
+            }
+        },
+        Some(("render", sub_matches)) => match run_render_from_matches(sub_matches, stdout) {
+            Ok(()) => 0,
+            Err(err) => {
+                let _ = writeln!(stderr, "Render error: {err:?}");
+                1
+            }
+        },
+        Some(("form", sub_matches)) => match run_form_from_matches(sub_matches) {
+            Ok(()) => 0,
+            Err(err) => {
+                let _ = writeln!(stderr, "Form error: {err:?}");
+                1
+            }
+        },
+        Some(("lint", sub_matches)) => {
+            let input_scad = sub_matches
+                .get_one::<String>("input-scad")
+                .expect("required");
+            match lint::run(Path::new(input_scad), stdout) {
+                Ok(findings) if findings.is_empty() || sub_matches.get_flag("warn-only") => 0,
+                Ok(_) => 1,
+                Err(err) => {
+                    let _ = writeln!(stderr, "Lint error: {err:?}");
+                    1
+                }
+            }
+        }
+        Some(("serve", sub_matches)) if sub_matches.get_flag("print-config") => {
+            match print_config(&matches, std::env::var("RUST_LOG").ok(), stdout) {
+                Ok(()) => 0,
+                Err(err) => {
+                    let _ = writeln!(stderr, "Config error: {err:?}");
+                    1
+                }
+            }
+        }
         Some(("serve", sub_matches)) => {
             if let Err(err) = serve_fn(sub_matches) {
                 let _ = writeln!(stderr, "Server error: {err:?}");
@@ -159,7 +610,9 @@ where
         _ => 1,
     };
 
-    let _ = writeln!(stderr, "");
+    if !quiet {
+        let _ = writeln!(stderr);
+    }
     exit_code
 }
 
@@ -167,6 +620,126 @@ where
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_listen_addr_accepts_bare_port() {
+        assert_eq!(
+            parse_listen_addr("3000", false).unwrap(),
+            "0.0.0.0:3000".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_listen_addr_accepts_host_and_port() {
+        assert_eq!(
+            parse_listen_addr("127.0.0.1:3000", false).unwrap(),
+            "127.0.0.1:3000".parse().unwrap()
+        );
+        assert_eq!(
+            parse_listen_addr("[::1]:8080", false).unwrap(),
+            "[::1]:8080".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_listen_addr_rejects_garbage() {
+        for value in ["localhost:http", "foo", "70000", "127.0.0.1", ":3000"] {
+            let err = parse_listen_addr(value, false).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("invalid --listen value '{value}': expected HOST:PORT or PORT")
+            );
+            // The parser's own reason stays in the chain.
+            assert!(err.chain().count() > 1, "{value}");
+        }
+    }
+
+    #[test]
+    fn parse_listen_addr_needs_port_file_for_port_zero() {
+        let err = parse_listen_addr("127.0.0.1:0", false).unwrap_err();
+        assert!(err.to_string().contains("--port-file"), "{err}");
+        assert!(parse_listen_addr("0", false).is_err());
+        assert_eq!(
+            parse_listen_addr("127.0.0.1:0", true).unwrap(),
+            "127.0.0.1:0".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn form_subcommand_writes_the_form_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let scad = dir.path().join("part.scad");
+        std::fs::write(
+            &scad,
+            "COASTER_D = 101.6; // @param\nMODE = \"base\"; // @param options: base|inlay\n",
+        )
+        .unwrap();
+        let out = dir.path().join("form.html");
+        let matches = cli::app()
+            .try_get_matches_from([
+                "openscad-part-maker",
+                "form",
+                "--input-scad",
+                scad.to_str().unwrap(),
+                "-o",
+                out.to_str().unwrap(),
+                "--api-url",
+                "https://parts.example.com/",
+            ])
+            .unwrap();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let code = run_once_with_serve(cli::app(), matches, |_| Ok(()), &mut stdout, &mut stderr);
+        assert_eq!(code, 0, "{}", String::from_utf8_lossy(&stderr));
+
+        let html = std::fs::read_to_string(&out).unwrap();
+        assert!(html.contains(r#"name="coaster_d" value="101.6""#));
+        assert!(html.contains(r#"<option value="inlay">"#));
+        assert!(html.contains(r#"action="https://parts.example.com/render""#));
+    }
+
+    #[test]
+    fn form_subcommand_matches_the_servers_field_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let scad = dir.path().join("part.scad");
+        std::fs::write(
+            &scad,
+            "COASTER_D = 101.6; // @param\nMODE = \"base\"; // @param options: base|inlay\n",
+        )
+        .unwrap();
+        let sets = dir.path().join("sets.json");
+        std::fs::write(
+            &sets,
+            r#"{"parameterSets": {"small": {"COASTER_D": "80"}}}"#,
+        )
+        .unwrap();
+        let out = dir.path().join("form.html");
+        let matches = cli::app()
+            .try_get_matches_from([
+                "openscad-part-maker",
+                "form",
+                "--input-scad",
+                scad.to_str().unwrap(),
+                "-o",
+                out.to_str().unwrap(),
+                "--param-prefix",
+                "scad_",
+                "--param-set",
+                sets.to_str().unwrap(),
+                "--expose",
+                "COASTER_D",
+            ])
+            .unwrap();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let code = run_once_with_serve(cli::app(), matches, |_| Ok(()), &mut stdout, &mut stderr);
+        assert_eq!(code, 0, "{}", String::from_utf8_lossy(&stderr));
+
+        let html = std::fs::read_to_string(&out).unwrap();
+        assert!(
+            html.contains(r#"name="scad_coaster_d" value="80.0""#),
+            "{html}"
+        );
+        assert!(!html.contains(r#"<option value="inlay">"#));
+    }
+
     #[test]
     fn determine_log_level_precedence_verbose_wins() {
         let cmd = cli::app();
@@ -189,6 +762,53 @@ mod tests {
         assert_eq!(lvl, "error");
     }
 
+    #[test]
+    fn print_config_shows_flags_overriding_env() {
+        let matches = cli::app()
+            .try_get_matches_from([
+                "openscad-part-maker",
+                "--log",
+                "error",
+                "serve",
+                "--input-scad",
+                "part.scad",
+                "--print-config",
+                "--listen",
+                "127.0.0.1:4000",
+                "--max-renders",
+                "2",
+                "--render-timeout",
+                "30",
+            ])
+            .unwrap();
+        let mut out = Vec::new();
+        print_config(&matches, Some("warn".into()), &mut out).unwrap();
+        let resolved: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(resolved["log_level"], "error");
+        assert_eq!(resolved["listen"], "127.0.0.1:4000");
+        assert_eq!(resolved["input_scad"], "part.scad");
+        assert_eq!(resolved["server"]["max_renders"], 2);
+        assert_eq!(resolved["server"]["render_timeout"], 30.0);
+        assert_eq!(resolved["server"]["slow_render"], 10.0);
+        assert_eq!(resolved["server"]["on_render"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn determine_log_format_defaults_to_text() {
+        let matches = cli::app()
+            .try_get_matches_from(["openscad-part-maker", "hello"])
+            .unwrap();
+        assert_eq!(determine_log_format(&matches), LogFormat::Text);
+
+        let matches = cli::app()
+            .try_get_matches_from(["openscad-part-maker", "hello", "--log-format", "json"])
+            .unwrap();
+        assert_eq!(determine_log_format(&matches), LogFormat::Json);
+
+        let res = cli::app().try_get_matches_from(["openscad-part-maker", "--log-format", "xml"]);
+        assert!(res.is_err());
+    }
+
     #[test]
     fn determine_log_level_uses_env_when_no_flags() {
         let cmd = cli::app();
@@ -241,6 +861,80 @@ mod tests {
         assert_eq!(String::from_utf8(out).unwrap().trim(), "Hello, Bob!");
     }
 
+    #[test]
+    fn run_once_quiet_skips_blank_line_padding() {
+        let cmd = cli::app();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["openscad-part-maker", "--quiet", "hello"])
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run_once_with_serve(cmd, matches, |_| Ok(()), &mut out, &mut err);
+        assert_eq!(code, 0);
+        assert!(
+            err.is_empty(),
+            "stderr: {:?}",
+            String::from_utf8_lossy(&err)
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "Hello, Bob!\n");
+
+        let cmd = cli::app();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["openscad-part-maker", "-q", "serve", "--input-scad", "x"])
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run_once_with_serve(cmd, matches, |_| anyhow::bail!("boom"), &mut out, &mut err);
+        assert_eq!(code, 1);
+        let err_s = String::from_utf8(err).unwrap();
+        assert!(err_s.starts_with("Server error"), "{err_s:?}");
+        assert!(!err_s.ends_with("\n\n"), "{err_s:?}");
+    }
+
+    #[test]
+    fn determine_log_level_quiet_below_explicit_flags() {
+        let matches = cli::app()
+            .try_get_matches_from(["openscad-part-maker", "-q", "hello"])
+            .unwrap();
+        assert_eq!(determine_log_level(&matches, Some("debug".into())), "warn");
+
+        let matches = cli::app()
+            .try_get_matches_from(["openscad-part-maker", "-q", "--log", "error", "hello"])
+            .unwrap();
+        assert_eq!(determine_log_level(&matches, None), "error");
+    }
+
+    #[test]
+    fn run_once_hello_repeat_prints_each_line() {
+        let cmd = cli::app();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["openscad-part-maker", "hello", "--repeat", "3"])
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run_once_with_serve(cmd, matches, |_| Ok(()), &mut out, &mut err);
+        assert_eq!(code, 0);
+        let out_s = String::from_utf8(out).unwrap();
+        assert_eq!(out_s.lines().collect::<Vec<_>>(), ["Hello, Bob!"; 3]);
+    }
+
+    #[test]
+    fn run_once_hello_uppercase() {
+        let cmd = cli::app();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["openscad-part-maker", "hello", "Ryan", "--uppercase"])
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run_once_with_serve(cmd, matches, |_| Ok(()), &mut out, &mut err);
+        assert_eq!(code, 0);
+        assert_eq!(String::from_utf8(out).unwrap().trim(), "HELLO, RYAN!");
+    }
+
     #[test]
     fn run_once_hello_custom_name() {
         let cmd = cli::app();
@@ -255,6 +949,178 @@ mod tests {
         assert!(String::from_utf8(out).unwrap().contains("Hello, Ryan!"));
     }
 
+    #[test]
+    fn run_once_lint_exit_code_follows_findings() {
+        let dir = tempfile::tempdir().unwrap();
+        let clean = dir.path().join("clean.scad");
+        let messy = dir.path().join("messy.scad");
+        std::fs::write(&clean, "WIDTH = 10; // @param\n").unwrap();
+        std::fs::write(&messy, "WIDTH = 10; // @param\nDEPTH = 4;\n").unwrap();
+
+        let run = |path: &Path, extra: &[&str]| {
+            let cmd = cli::app();
+            let mut argv = vec!["openscad-part-maker", "lint", "--input-scad"];
+            argv.push(path.to_str().unwrap());
+            argv.extend(extra);
+            let matches = cmd.clone().try_get_matches_from(argv).unwrap();
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let code = run_once_with_serve(cmd, matches, |_| Ok(()), &mut out, &mut err);
+            (code, String::from_utf8(out).unwrap())
+        };
+
+        let (code, out) = run(&clean, &[]);
+        assert_eq!(code, 0);
+        assert!(out.contains("0 finding(s)"), "{out}");
+
+        let (code, out) = run(&messy, &[]);
+        assert_eq!(code, 1);
+        assert!(out.contains("DEPTH: literal default but no"), "{out}");
+        assert_eq!(run(&messy, &["--warn-only"]).0, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn render_batch_and_form_search_the_scad_lib_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let libs = dir.path().join("libs");
+        std::fs::create_dir(&libs).unwrap();
+        std::fs::write(libs.join("shapes.scad"), "WIDTH = 10; // @param\n").unwrap();
+        let scad = dir.path().join("input.scad");
+        std::fs::write(&scad, "include <shapes.scad>;\n").unwrap();
+        std::fs::write(dir.path().join("part.svg"), "<svg/>").unwrap();
+        let jobs = dir.path().join("jobs.json");
+        std::fs::write(&jobs, r#"[{"svg": "part.svg", "params": {"width": 20}}]"#).unwrap();
+
+        // Records OPENSCADPATH and its args, then writes an STL big enough
+        // to pass the empty-geometry check.
+        let seen = dir.path().join("seen");
+        let openscad = dir.path().join("fake-openscad");
+        std::fs::write(
+            &openscad,
+            format!(
+                "#!/bin/sh\necho \"$OPENSCADPATH $*\" >> \"{}\"\n\
+                 while [ $# -gt 0 ]; do [ \"$1\" = -o ] && out=\"$2\"; shift; done\n\
+                 head -c 200 /dev/zero > \"$out\"\n",
+                seen.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&openscad, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let run = |args: &[&str]| {
+            let mut argv = vec!["openscad-part-maker"];
+            argv.extend(args);
+            argv.extend(["--input-scad", scad.to_str().unwrap()]);
+            argv.extend(["--scad-lib-path", libs.to_str().unwrap()]);
+            let matches = cli::app().try_get_matches_from(argv).unwrap();
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let code = run_once_with_serve(cli::app(), matches, |_| Ok(()), &mut out, &mut err);
+            assert_eq!(code, 0, "{}", String::from_utf8_lossy(&err));
+        };
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let bin = openscad.to_str().unwrap();
+        let (svg, stl, out_dir, form) = (
+            path("part.svg"),
+            path("one.stl"),
+            path("out"),
+            path("form.html"),
+        );
+
+        run(&["render", "--openscad-bin", bin, "--svg", &svg, "-o", &stl]);
+        let jobs = jobs.to_str().unwrap();
+        run(&[
+            "batch",
+            "--openscad-bin",
+            bin,
+            "--jobs",
+            jobs,
+            "--out-dir",
+            &out_dir,
+        ]);
+        run(&["form", "-o", &form]);
+
+        let seen = std::fs::read_to_string(&seen).unwrap();
+        let lines: Vec<&str> = seen.lines().collect();
+        assert_eq!(lines.len(), 2, "{seen}");
+        for line in &lines {
+            assert!(line.starts_with(libs.to_str().unwrap()), "{line}");
+        }
+        assert!(lines[0].contains("WIDTH=10"), "{seen}");
+        assert!(lines[1].contains("WIDTH=20"), "{seen}");
+        let html = std::fs::read_to_string(&form).unwrap();
+        assert!(html.contains(r#"name="width""#));
+    }
+
+    #[test]
+    fn run_once_batch_uses_the_given_openscad_bin() {
+        let dir = tempfile::tempdir().unwrap();
+        let scad = dir.path().join("input.scad");
+        std::fs::write(&scad, "WIDTH = 10; // @param\n").unwrap();
+        std::fs::write(dir.path().join("part.svg"), "<svg/>").unwrap();
+        let jobs = dir.path().join("jobs.json");
+        std::fs::write(&jobs, r#"[{"svg": "part.svg"}]"#).unwrap();
+        let missing = dir.path().join("no-such-openscad");
+
+        let cmd = cli::app();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from([
+                "openscad-part-maker",
+                "batch",
+                "--input-scad",
+                scad.to_str().unwrap(),
+                "--jobs",
+                jobs.to_str().unwrap(),
+                "--out-dir",
+                dir.path().to_str().unwrap(),
+                "--openscad-bin",
+                missing.to_str().unwrap(),
+            ])
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run_once_with_serve(cmd, matches, |_| Ok(()), &mut out, &mut err);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(code, 1);
+        assert!(out.contains("no-such-openscad"), "{out}");
+    }
+
+    #[test]
+    fn run_once_render_uses_the_given_openscad_bin() {
+        let dir = tempfile::tempdir().unwrap();
+        let scad = dir.path().join("input.scad");
+        std::fs::write(&scad, "WIDTH = 10; // @param\n").unwrap();
+        let svg = dir.path().join("part.svg");
+        std::fs::write(&svg, "<svg/>").unwrap();
+        let missing = dir.path().join("no-such-openscad");
+
+        let cmd = cli::app();
+        let matches = cmd
+            .clone()
+            .try_get_matches_from([
+                "openscad-part-maker",
+                "render",
+                "--input-scad",
+                scad.to_str().unwrap(),
+                "--svg",
+                svg.to_str().unwrap(),
+                "--openscad-bin",
+                missing.to_str().unwrap(),
+                "-o",
+                dir.path().join("part.stl").to_str().unwrap(),
+            ])
+            .unwrap();
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let code = run_once_with_serve(cmd, matches, |_| Ok(()), &mut out, &mut err);
+        let err = String::from_utf8(err).unwrap();
+        assert_eq!(code, 1);
+        assert!(err.contains("no-such-openscad"), "{err}");
+    }
+
     #[test]
     fn run_once_completions_without_shell_exits_one_and_prints_instructions() {
         let cmd = cli::app();
@@ -286,6 +1152,22 @@ mod tests {
         assert!(!out.is_empty());
     }
 
+    #[test]
+    fn run_once_completions_powershell_and_elvish() {
+        for shell in ["powershell", "elvish"] {
+            let cmd = cli::app();
+            let matches = cmd
+                .clone()
+                .try_get_matches_from(["openscad-part-maker", "completions", shell])
+                .unwrap();
+            let mut out = Vec::new();
+            let mut err = Vec::new();
+            let code = run_once_with_serve(cmd, matches, |_| Ok(()), &mut out, &mut err);
+            assert_eq!(code, 0, "{shell}");
+            assert!(!out.is_empty(), "{shell}");
+        }
+    }
+
     #[test]
     fn run_once_serve_success_path_exits_zero() {
         let cmd = cli::app();
@@ -0,0 +1,449 @@
+//! OpenAPI 3 description of the HTTP API, served at `GET /openapi.json`.
+//!
+//! Hand-written rather than generated: the multipart contract is small, and
+//! the template's params are filled in from the loaded `.scad` so clients see
+//! the fields this server actually accepts.
+
+use serde_json::{Map, Value, json};
+
+use crate::format::COLORSCHEMES;
+use crate::scad_params::{ParamType, ScadParamTemplate, is_svg_define};
+
+/// Build the document for a server running `template`.
+pub fn document(template: &ScadParamTemplate) -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "openscad-part-maker",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Render OpenSCAD parts from an uploaded SVG and template parameters.",
+        },
+        "paths": {
+            "/render": {
+                "post": {
+                    "summary": "Render a part from an uploaded SVG",
+                    "description": "Send `Accept: application/json` to get a RenderSummary and a download token instead of the file.",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "multipart/form-data": {
+                                "schema": { "$ref": "#/components/schemas/RenderForm" },
+                            },
+                        },
+                    },
+                    "responses": render_responses(),
+                },
+                "get": {
+                    "summary": "Render the template defaults with the server's --default-svg",
+                    "description": "Any RenderForm param field may also be given in the query string (e.g. `?coaster_d=90&mode=inlay`) to override a default.",
+                    "parameters": [{
+                        "name": "format",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "$ref": "#/components/schemas/ExportFormat" },
+                    }],
+                    "responses": render_responses(),
+                },
+            },
+            "/preview": {
+                "post": {
+                    "summary": "Render a PNG image of the part",
+                    "description": "Takes the /render form; the format is always png. `colorscheme` defaults to the template's `// @colorscheme`. OpenSCAD has no background option of its own; the background is the color scheme's.",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "multipart/form-data": {
+                                "schema": { "$ref": "#/components/schemas/RenderForm" },
+                            },
+                        },
+                    },
+                    "responses": render_responses(),
+                },
+            },
+            "/thumbnails": {
+                "post": {
+                    "summary": "Render a PNG per MODE option, for a gallery",
+                    "description": "Takes the /preview form. Each image is a multipart part named after its mode; at most 8 modes are rendered.",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "multipart/form-data": {
+                                "schema": { "$ref": "#/components/schemas/RenderForm" },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "One image/png part per mode",
+                            "content": { "multipart/form-data": {} },
+                        },
+                        "400": { "description": "The template has no MODE options, or an invalid field" },
+                    },
+                },
+            },
+            "/compare": {
+                "post": {
+                    "summary": "Render two param sets and answer both, for A/B comparison",
+                    "description": "Takes the /render form plus `params_a` and `params_b`, JSON objects of param fields applied over the form's own for each side.",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "multipart/form-data": {
+                                "schema": { "$ref": "#/components/schemas/RenderForm" },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "ZIP holding a.<ext> and b.<ext>",
+                            "content": { "application/zip": { "schema": { "type": "string", "format": "binary" } } },
+                        },
+                        "400": { "description": "A missing params_a/params_b, or an invalid field" },
+                    },
+                },
+            },
+            "/estimate": {
+                "post": {
+                    "summary": "Estimate the material a part takes",
+                    "description": "Takes the /render form plus `density` in g/cm³ (default 1.24, PLA); the part is always rendered as STL. Infill isn't accounted for.",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "multipart/form-data": {
+                                "schema": { "$ref": "#/components/schemas/RenderForm" },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "`{\"volume_mm3\": V, \"approx_grams\": G}`",
+                            "content": { "application/json": {} },
+                        },
+                        "400": { "description": "An invalid field" },
+                    },
+                },
+            },
+            "/download/{token}": {
+                "get": {
+                    "summary": "Fetch a render stashed by a JSON /render call (one-shot)",
+                    "parameters": [{
+                        "name": "token",
+                        "in": "path",
+                        "required": true,
+                        "schema": { "type": "string" },
+                    }],
+                    "responses": {
+                        "200": { "description": "The rendered file", "content": file_content() },
+                        "404": { "description": "Unknown or expired token" },
+                    },
+                },
+            },
+            "/uploads": {
+                "post": {
+                    "summary": "Start a resumable SVG upload (tus subset)",
+                    "description": "Send chunks with PATCH to the returned Location, then render with `svg_upload` set to its id. Unfinished uploads expire an hour after their last chunk.",
+                    "parameters": [{
+                        "name": "Upload-Length",
+                        "in": "header",
+                        "required": true,
+                        "schema": { "type": "integer" },
+                    }],
+                    "responses": {
+                        "201": { "description": "Created; `Location` is the upload's URL" },
+                        "400": { "description": "A missing or invalid Upload-Length" },
+                        "413": { "description": "Upload-Length is over --max-body-bytes" },
+                        "503": { "description": "Too many uploads in progress; retry later" },
+                    },
+                },
+            },
+            "/uploads/{id}": {
+                "parameters": [{
+                    "name": "id",
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                }],
+                "head": {
+                    "summary": "How much of the upload arrived, to resume from",
+                    "responses": {
+                        "200": { "description": "`Upload-Offset` and `Upload-Length` headers" },
+                        "404": { "description": "Unknown or expired upload" },
+                    },
+                },
+                "patch": {
+                    "summary": "Append a chunk at Upload-Offset",
+                    "parameters": [{
+                        "name": "Upload-Offset",
+                        "in": "header",
+                        "required": true,
+                        "schema": { "type": "integer" },
+                    }],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/offset+octet-stream": {
+                                "schema": { "type": "string", "format": "binary" },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "204": { "description": "Appended; `Upload-Offset` is the new offset" },
+                        "404": { "description": "Unknown or expired upload" },
+                        "409": { "description": "Upload-Offset isn't where the upload left off" },
+                        "415": { "description": "The body isn't application/offset+octet-stream" },
+                    },
+                },
+            },
+            "/api/params": {
+                "get": {
+                    "summary": "List the template's user-facing params",
+                    "responses": {
+                        "200": {
+                            "description": "Discovered params",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "array",
+                                        "items": { "$ref": "#/components/schemas/ParamSpec" },
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+            "/livez": {
+                "get": {
+                    "summary": "Liveness probe: the process is serving",
+                    "responses": { "200": { "description": "Up" } },
+                },
+            },
+            "/readyz": {
+                "get": {
+                    "summary": "Readiness probe: OpenSCAD can be run",
+                    "responses": {
+                        "200": { "description": "Ready to render" },
+                        "503": { "description": "OpenSCAD is not reachable" },
+                    },
+                },
+            },
+            "/admin/reload": {
+                "post": {
+                    "summary": "Re-read the template and swap it in (only with --admin-token)",
+                    "description": "Send `Authorization: Bearer <token>`. Answers the new template's param counts.",
+                    "responses": {
+                        "200": { "description": "`{\"params\": N, \"user_params\": M}`" },
+                        "401": { "description": "Missing or wrong admin token" },
+                        "422": { "description": "The template failed to load; the old one stays" },
+                    },
+                },
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "This document",
+                    "responses": { "200": { "description": "OpenAPI 3 document" } },
+                },
+            },
+        },
+        "components": {
+            "schemas": {
+                "ExportFormat": { "type": "string", "enum": ["stl", "dxf", "svg", "png"] },
+                "RenderForm": render_form_schema(template),
+                "RenderSummary": {
+                    "type": "object",
+                    "properties": {
+                        "filename": { "type": "string" },
+                        "bytes": { "type": "integer" },
+                        "triangles": { "type": "integer", "nullable": true },
+                        "download_token": { "type": "string" },
+                        "echo": { "type": "array", "items": { "type": "string" }, "description": "echo() output, with echo=true" },
+                    },
+                    "required": ["filename", "bytes", "download_token"],
+                },
+                "ParamSpec": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "default": { "type": "string", "description": "OpenSCAD literal" },
+                        "ty": { "type": "string", "enum": ["number", "integer", "bool", "string", "file"] },
+                        "is_user_param": { "type": "boolean" },
+                        "comment": { "type": "string" },
+                        "options": { "type": "array", "items": { "type": "string" } },
+                        "advanced": { "type": "boolean" },
+                        "form_default": { "type": "string", "nullable": true, "description": "OpenSCAD literal the form is pre-filled with" },
+                        "step": { "type": "number", "nullable": true },
+                        "enforce_step": { "type": "boolean", "description": "Numbers off the step grid are rejected" },
+                        "computed": { "type": "number", "nullable": true, "description": "Value of a derived default expression, for display" },
+                        "required": { "type": "boolean", "description": "The default is an expression with no usable value; requests must set it" },
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn file_content() -> Value {
+    let binary = json!({ "schema": { "type": "string", "format": "binary" } });
+    json!({
+        "model/stl": binary,
+        "image/vnd.dxf": binary,
+        "image/svg+xml": binary,
+        "image/png": binary,
+    })
+}
+
+fn render_responses() -> Value {
+    let mut content = file_content();
+    content["application/json"] =
+        json!({ "schema": { "$ref": "#/components/schemas/RenderSummary" } });
+    json!({
+        "200": { "description": "The rendered file", "content": content },
+        "400": { "description": "Missing or invalid SVG, param or format" },
+        "413": { "description": "The body is over --max-body-bytes or an SVG over --max-svg-bytes" },
+        "422": { "description": "The render produced no geometry" },
+        "500": { "description": "OpenSCAD failed" },
+        "503": { "description": "OpenSCAD is not installed, or the per-minute render budget is used up (see Retry-After)" },
+        "504": { "description": "The render timed out" },
+    })
+}
+
+/// Fixed fields plus one property per user-facing template param.
+fn render_form_schema(template: &ScadParamTemplate) -> Value {
+    let mut properties = Map::new();
+    properties.insert(
+        "svg".into(),
+        json!({ "type": "string", "format": "binary", "description": "SVG passed as SVG_PATH" }),
+    );
+    properties.insert(
+        "svg_asset".into(),
+        json!({ "type": "string", "description": "SVG path under the server's --allow-svg-dir, instead of an svg upload" }),
+    );
+    properties.insert(
+        "svg_upload".into(),
+        json!({ "type": "string", "description": "Id of a finished POST /uploads upload, instead of an svg upload" }),
+    );
+    properties.insert(
+        "name".into(),
+        json!({ "type": "string", "description": "Output name; defaults to the SVG file stem" }),
+    );
+    properties.insert("fs".into(), json!({ "type": "number", "default": 0.1 }));
+    properties.insert("fa".into(), json!({ "type": "number", "default": 5 }));
+    properties.insert("fn".into(), json!({ "type": "integer", "default": 200 }));
+    properties.insert(
+        "format".into(),
+        json!({ "$ref": "#/components/schemas/ExportFormat" }),
+    );
+    properties.insert(
+        "scale".into(),
+        json!({ "type": "number", "default": 1, "description": "Multiply the STL's vertices, e.g. 0.03937 (1/25.4) to convert mm to inches" }),
+    );
+    for side in ["a", "b"] {
+        properties.insert(
+            format!("params_{side}"),
+            json!({ "type": "string", "description": format!("JSON object of param fields for side {side} of /compare") }),
+        );
+    }
+    properties.insert(
+        "density".into(),
+        json!({ "type": "number", "default": 1.24, "description": "Material density in g/cm³, for /estimate" }),
+    );
+    properties.insert(
+        "print_ready".into(),
+        json!({ "type": "boolean", "default": false, "description": "Rotate the STL to lie on its largest flat face" }),
+    );
+    properties.insert(
+        "quality".into(),
+        json!({
+            "type": "string",
+            "enum": ["exact", "auto"],
+            "default": "exact",
+            "description": "auto: retry a timed-out render at preview quality and set X-Quality: degraded",
+        }),
+    );
+    properties.insert(
+        "colorscheme".into(),
+        json!({
+            "type": "string",
+            "enum": COLORSCHEMES,
+            "description": "OpenSCAD color scheme for PNG output, background included; ignored for other formats",
+        }),
+    );
+    properties.insert(
+        "camera".into(),
+        json!({
+            "type": "string",
+            "description": "PNG camera: `auto` to frame the whole part, or an OpenSCAD --camera value (6 or 7 numbers)",
+        }),
+    );
+    properties.insert(
+        "echo".into(),
+        json!({ "type": "boolean", "default": false, "description": "Include the template's echo() output in the JSON summary" }),
+    );
+    properties.insert(
+        "params".into(),
+        json!({ "type": "string", "description": "JSON object of param fields; individual fields win. Settings such as fn or format must be sent as their own fields" }),
+    );
+
+    let mut required = vec![json!("svg")];
+    for spec in template.specs.values() {
+        if !spec.is_user_param || spec.name == "NAME" || is_svg_define(&spec.name) {
+            continue;
+        }
+        let field = format!(
+            "{}{}",
+            template.field_prefix,
+            spec.name.to_ascii_lowercase()
+        );
+        let mut schema = match spec.ty {
+            ParamType::Number => json!({ "type": "number" }),
+            ParamType::Integer => json!({ "type": "integer" }),
+            ParamType::Bool => json!({ "type": "boolean" }),
+            ParamType::String if spec.options.is_empty() => json!({ "type": "string" }),
+            ParamType::String => json!({ "type": "string", "enum": spec.options }),
+            ParamType::File => json!({ "type": "string", "format": "binary" }),
+        };
+        schema["x-scad-name"] = json!(spec.name);
+        schema["x-scad-default"] = json!(spec.default);
+        if spec.required {
+            required.push(json!(field));
+        }
+        properties.insert(field, schema);
+    }
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_lists_render_and_template_fields() {
+        let mut tmpl = ScadParamTemplate::from_scad_text(
+            r#"
+COASTER_D=101.6; // @param
+SHAPE="octagon"; // @param options: octagon|circle
+FIT=0.2;
+"#,
+        )
+        .unwrap();
+        tmpl.field_prefix = "scad_".to_string();
+
+        let text = serde_json::to_string(&document(&tmpl)).unwrap();
+        let doc: Value = serde_json::from_str(&text).unwrap();
+
+        assert_eq!(doc["openapi"], "3.0.3");
+        assert!(doc["paths"]["/render"]["post"].is_object());
+        assert!(doc["paths"]["/api/params"]["get"].is_object());
+
+        let props = &doc["components"]["schemas"]["RenderForm"]["properties"];
+        assert_eq!(props["svg"]["format"], "binary");
+        assert_eq!(props["scad_coaster_d"]["type"], "number");
+        assert_eq!(props["scad_shape"]["enum"], json!(["octagon", "circle"]));
+        assert!(props.get("scad_fit").is_none());
+    }
+}
@@ -0,0 +1,91 @@
+//! Render permits (see `--max-renders`) with a count of the requests waiting
+//! for one, so a blocked client can be told where it stands.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{AcquireError, Semaphore, SemaphorePermit};
+
+pub struct RenderQueue {
+    slots: Semaphore,
+    waiting: AtomicUsize,
+}
+
+impl RenderQueue {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            slots: Semaphore::new(permits),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// A queue that never makes anyone wait.
+    pub fn unlimited() -> Self {
+        Self::new(Semaphore::MAX_PERMITS)
+    }
+
+    /// Requests currently blocked in `acquire`.
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Take a permit, waiting for one if all are in use. Also returns the
+    /// caller's place in line when it started waiting: 0 if a permit was
+    /// free, 1 if it was first in line, and so on.
+    pub async fn acquire(&self) -> Result<(SemaphorePermit<'_>, usize), AcquireError> {
+        if let Ok(permit) = self.slots.try_acquire() {
+            return Ok((permit, 0));
+        }
+        let position = self.waiting.fetch_add(1, Ordering::SeqCst) + 1;
+        // Counted back down even if the request is dropped while waiting.
+        let _waiter = Waiter(&self.waiting);
+        let permit = self.slots.acquire().await?;
+        Ok((permit, position))
+    }
+}
+
+struct Waiter<'a>(&'a AtomicUsize);
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn waiters_are_counted_until_they_get_a_permit() {
+        let queue = RenderQueue::new(1);
+        let (held, position) = queue.acquire().await.unwrap();
+        assert_eq!(position, 0);
+        assert_eq!(queue.waiting(), 0);
+
+        let first = queue.acquire();
+        let second = queue.acquire();
+        tokio::pin!(first, second);
+        let wait = Duration::from_millis(20);
+        assert!(tokio::time::timeout(wait, &mut first).await.is_err());
+        assert!(tokio::time::timeout(wait, &mut second).await.is_err());
+        assert_eq!(queue.waiting(), 2);
+
+        drop(held);
+        let (permit, position) = first.await.unwrap();
+        assert_eq!(position, 1);
+        assert_eq!(queue.waiting(), 1);
+        drop(permit);
+        let (_permit, position) = second.await.unwrap();
+        assert_eq!(position, 2);
+        assert_eq!(queue.waiting(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiters_leave_the_count() {
+        let queue = RenderQueue::new(1);
+        let _held = queue.acquire().await.unwrap();
+        let wait = Duration::from_millis(20);
+        assert!(tokio::time::timeout(wait, queue.acquire()).await.is_err());
+        assert_eq!(queue.waiting(), 0);
+    }
+}
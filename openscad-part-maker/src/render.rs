@@ -0,0 +1,1552 @@
+//! The OpenSCAD render pipeline: argument assembly, the child process, and
+//! the `Renderer` that the server and batch runner drive.
+
+use anyhow::{Context, bail};
+use std::{
+    fmt,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use tokio::process::Command;
+
+use crate::format::ExportFormat;
+use crate::prelude::*;
+use crate::scad_params::{ScadParamTemplate, ScadParams};
+use crate::stl;
+
+/// Tiny stand-in SVG for renders that have no upload (e.g. preflight).
+pub const PLACEHOLDER_SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10" viewBox="0 0 10 10"><rect width="10" height="10"/></svg>"#;
+
+/// Minimal DXF with no entities, returned by fake renders.
+const PLACEHOLDER_DXF: &str = "0\nSECTION\n2\nENTITIES\n0\nENDSEC\n0\nEOF\n";
+
+/// A 1x1 white PNG, returned by fake renders.
+const PLACEHOLDER_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53,
+    0xde, 0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8, 0xff, 0xff, 0x29,
+    0x00, 0x05, 0xe4, 0x02, 0xe4, 0x34, 0xb3, 0x78, 0xe2, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e,
+    0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+/// Segment count used for preflight renders; only compilation matters.
+const PREFLIGHT_FN: i32 = 12;
+
+/// First release with `--check-parameters` / `--check-parameter-ranges`.
+const CHECK_PARAMETERS_SINCE: OpenscadVersion = (2021, 1);
+
+/// Experimental features recent OpenSCAD releases accept with `--enable`.
+pub const KNOWN_FEATURES: &[&str] = &[
+    "fast-csg",
+    "fast-csg-exact",
+    "fast-csg-exact-callbacks",
+    "fast-csg-remesh",
+    "fast-csg-safer",
+    "fast-csg-trust-corefinement",
+    "import-function",
+    "input-driver-dbus",
+    "lazy-union",
+    "manifold",
+    "object-function",
+    "predictible-output",
+    "roof",
+    "sort-stl",
+    "textmetrics",
+    "vertex-object-renderers",
+    "vertex-object-renderers-direct",
+    "vertex-object-renderers-indexing",
+    "vertex-object-renderers-prealloc",
+];
+
+/// Wait before the first retry of a transient failure; doubles each time.
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Exit codes a wrapper shell reports for OpenSCAD dying of SIGABRT (usually
+/// a failed allocation) or SIGKILL (the OOM killer).
+const TRANSIENT_EXIT_CODES: [i32; 2] = [134, 137];
+
+/// Curve resolution, passed to the template as `fs`, `fa` and `fn`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quality {
+    pub fs: f32,
+    pub fa: f32,
+    pub fn_: i32,
+}
+
+impl Default for Quality {
+    fn default() -> Self {
+        Self {
+            fs: 0.1,
+            fa: 5.0,
+            fn_: 200,
+        }
+    }
+}
+
+/// Coarsest settings `quality=auto` falls back to after a timeout.
+pub const PREVIEW_QUALITY: Quality = Quality {
+    fs: 1.0,
+    fa: 12.0,
+    fn_: 50,
+};
+
+impl Quality {
+    /// These settings capped at `PREVIEW_QUALITY`, or `None` if they are
+    /// already no finer than it (so a retry would take just as long).
+    pub fn coarsened(self) -> Option<Quality> {
+        let coarse = Quality {
+            fs: self.fs.max(PREVIEW_QUALITY.fs),
+            fa: self.fa.max(PREVIEW_QUALITY.fa),
+            fn_: self.fn_.min(PREVIEW_QUALITY.fn_),
+        };
+        (coarse != self).then_some(coarse)
+    }
+}
+
+/// How a PNG is drawn. Only PNG renders pass these flags to OpenSCAD; the
+/// other exporters would ignore them. There is no background setting:
+/// OpenSCAD takes the background from the color scheme.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PreviewOptions {
+    /// `--colorscheme`, one of `format::COLORSCHEMES`.
+    pub colorscheme: Option<&'static str>,
+    /// Where the camera looks from; OpenSCAD's default view if unset.
+    pub camera: Option<Camera>,
+}
+
+/// A preview's camera.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Camera {
+    /// `--viewall --autocenter`: frame the whole part, whatever its size.
+    Auto,
+    /// `--camera=` value, passed through as given: six numbers
+    /// (translate and rotate, then distance) or seven (eye, center, distance).
+    Explicit(String),
+}
+
+impl FromStr for Camera {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Camera::Auto);
+        }
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        if !matches!(parts.len(), 6 | 7) || parts.iter().any(|p| p.parse::<f64>().is_err()) {
+            return Err(format!(
+                "expected \"auto\" or 6 or 7 comma-separated numbers, got {s:?}"
+            ));
+        }
+        Ok(Camera::Explicit(s.to_string()))
+    }
+}
+
+impl PreviewOptions {
+    /// OpenSCAD flags for a render to `format`.
+    pub fn args(&self, format: ExportFormat) -> Vec<String> {
+        let mut args = Vec::new();
+        if format != ExportFormat::Png {
+            return args;
+        }
+        if let Some(scheme) = self.colorscheme {
+            args.push("--colorscheme".into());
+            args.push(scheme.into());
+        }
+        match &self.camera {
+            Some(Camera::Auto) => {
+                args.push("--viewall".into());
+                args.push("--autocenter".into());
+            }
+            Some(Camera::Explicit(camera)) => args.push(format!("--camera={camera}")),
+            None => {}
+        }
+        args
+    }
+}
+
+/// Per-renderer settings.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Top-level `.scad` file handed to OpenSCAD.
+    pub input_scad_path: PathBuf,
+    /// Quality used by `Renderer::render`.
+    pub quality: Quality,
+    /// Pin threads/seeds and canonicalize STL output (see `stl::canonicalize`).
+    pub deterministic: bool,
+    /// Kill OpenSCAD if a render runs longer than this.
+    pub timeout: Option<Duration>,
+    /// Extra attempts after a transient failure (see `RenderError::is_transient`).
+    pub retries: u32,
+    /// Additional OpenSCAD flags (e.g. from `strict_args`), placed after `--render`.
+    pub extra_args: Vec<String>,
+    /// Skip OpenSCAD and return placeholder output (see `fake_output`).
+    pub fake: bool,
+    /// Extra environment for the OpenSCAD child (e.g. from `--env-file`).
+    pub env: Vec<(String, String)>,
+    /// `OPENSCAD_THREADS` for the child; overrides `env`.
+    pub threads: Option<u32>,
+    /// Niceness for the child (Unix only): 0 is normal, 19 the lowest priority.
+    pub nice: Option<i32>,
+    /// Address-space limit for the child in MiB (Unix only, `RLIMIT_AS`).
+    pub mem_limit_mib: Option<u64>,
+    /// Refuse renders whose workdir is inside the template's directory, so
+    /// nothing is ever written next to the template (see `guard_workdir`).
+    pub read_only_template: bool,
+    /// Wrapper argv OpenSCAD is run under (e.g. `bwrap ... --`); see
+    /// `sandbox_argv` for the placeholders it may use.
+    pub sandbox_cmd: Vec<String>,
+    /// Experimental features, each passed as `--enable <name>`.
+    pub features: Vec<String>,
+    /// Library directories (`--scad-lib-path`), searched by the child ahead
+    /// of any `OPENSCADPATH` it would otherwise get (see `openscad_path`).
+    pub lib_paths: Vec<PathBuf>,
+}
+
+impl RenderOptions {
+    pub fn new(input_scad_path: impl Into<PathBuf>) -> Self {
+        Self {
+            input_scad_path: input_scad_path.into(),
+            quality: Quality::default(),
+            deterministic: false,
+            timeout: None,
+            retries: 0,
+            extra_args: Vec::new(),
+            fake: false,
+            env: Vec::new(),
+            threads: None,
+            nice: None,
+            mem_limit_mib: None,
+            read_only_template: false,
+            sandbox_cmd: Vec::new(),
+            features: Vec::new(),
+            lib_paths: Vec::new(),
+        }
+    }
+}
+
+/// OpenSCAD release as `(year, month)`, e.g. 2021.01 is `(2021, 1)`.
+pub type OpenscadVersion = (u32, u32);
+
+/// Find the release in `openscad --version` output ("OpenSCAD version 2021.01").
+pub fn parse_openscad_version(output: &str) -> Option<OpenscadVersion> {
+    let rest = &output[output.find("version")? + "version".len()..];
+    let mut parts = rest.trim_start().split(|c: char| !c.is_ascii_digit());
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    Some((year, month))
+}
+
+/// Ask `openscad_bin` for its version; `None` if it can't be run or parsed.
+pub async fn detect_openscad_version(openscad_bin: &Path) -> Option<OpenscadVersion> {
+    let output = Command::new(openscad_bin)
+        .arg("--version")
+        .output()
+        .await
+        .ok()?;
+    // OpenSCAD prints its version on stderr.
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stderr),
+        String::from_utf8_lossy(&output.stdout)
+    );
+    parse_openscad_version(&text)
+}
+
+/// Flags that turn OpenSCAD warnings into errors. The parameter checks are
+/// only passed to releases known to have them.
+pub fn strict_args(version: Option<OpenscadVersion>) -> Vec<String> {
+    let mut args = vec!["--hardwarnings".to_string()];
+    if version.is_some_and(|v| v >= CHECK_PARAMETERS_SINCE) {
+        args.extend(
+            [
+                "--check-parameters",
+                "true",
+                "--check-parameter-ranges",
+                "true",
+            ]
+            .map(String::from),
+        );
+    }
+    args
+}
+
+/// Check an `--enable-feature` name. Names missing from `KNOWN_FEATURES`
+/// are still allowed, since newer OpenSCAD releases add features, but come
+/// back as `Ok(false)` so the caller can warn about them.
+pub fn check_feature(name: &str) -> Result<bool, String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("{name:?}: not a feature name"));
+    }
+    Ok(KNOWN_FEATURES.contains(&name))
+}
+
+/// Why a render produced no output.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The OpenSCAD executable doesn't exist.
+    BinaryNotFound(PathBuf),
+    /// OpenSCAD ran past `RenderOptions::timeout` and was killed.
+    Timeout(Duration),
+    /// OpenSCAD exited unsuccessfully; `code` is `None` if it was killed by
+    /// a signal, and `sigkilled` says whether that signal was SIGKILL.
+    NonZeroExit {
+        code: Option<i32>,
+        sigkilled: bool,
+        stderr: String,
+    },
+    /// OpenSCAD exited successfully but wrote no output file.
+    OutputMissing(PathBuf),
+    /// The STL is too small to hold a single triangle: the geometry was empty.
+    EmptyGeometry { len: usize },
+    /// OpenSCAD ran out of address space under `RenderOptions::mem_limit_mib`.
+    MemoryLimit { limit_mib: u64 },
+    /// OpenSCAD couldn't be started (other than not being installed).
+    Spawn(std::io::Error),
+    /// Moving files in and out of the work dir failed.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::BinaryNotFound(bin) => {
+                write!(f, "openscad binary not found: {}", bin.display())
+            }
+            RenderError::Timeout(timeout) => write!(f, "render timed out after {timeout:?}"),
+            RenderError::NonZeroExit {
+                code: Some(code), ..
+            } => write!(f, "openscad exited with code {code}"),
+            RenderError::NonZeroExit { code: None, .. } => {
+                write!(f, "openscad was killed by a signal")
+            }
+            RenderError::OutputMissing(path) => {
+                write!(f, "openscad wrote no output to {}", path.display())
+            }
+            RenderError::EmptyGeometry { len } => {
+                write!(f, "render produced empty geometry ({len} byte STL)")
+            }
+            RenderError::MemoryLimit { limit_mib } => {
+                write!(f, "render exceeded the {limit_mib} MiB memory limit")
+            }
+            RenderError::Spawn(err) => write!(f, "could not run openscad: {err}"),
+            RenderError::Io(err) => write!(f, "render I/O error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Spawn(err) | RenderError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl RenderError {
+    /// True for failures caused by the machine rather than the input, which
+    /// may succeed if tried again: OpenSCAD failing to start, or dying of an
+    /// allocation failure or SIGKILL. Bad geometry fails the same way every
+    /// time, including when it crashes CGAL with a SIGSEGV.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RenderError::Spawn(_) => true,
+            RenderError::NonZeroExit {
+                code, sigkilled, ..
+            } => *sigkilled || code.is_some_and(|code| TRANSIENT_EXIT_CODES.contains(&code)),
+            RenderError::BinaryNotFound(_)
+            | RenderError::Timeout(_)
+            | RenderError::OutputMissing(_)
+            | RenderError::EmptyGeometry { .. }
+            | RenderError::MemoryLimit { .. }
+            | RenderError::Io(_) => false,
+        }
+    }
+}
+
+/// Run `attempt`, retrying transient failures up to `retries` times and
+/// doubling the wait (starting at `backoff`) between tries.
+pub async fn with_retries<T, F, Fut>(
+    retries: u32,
+    backoff: Duration,
+    mut attempt: F,
+) -> Result<T, RenderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RenderError>>,
+{
+    let mut delay = backoff;
+    let mut tries_left = retries;
+    loop {
+        match attempt().await {
+            Err(err) if tries_left > 0 && err.is_transient() => {
+                warn!("Render failed ({err}); retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                tries_left -= 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+impl From<std::io::Error> for RenderError {
+    fn from(err: std::io::Error) -> Self {
+        RenderError::Io(err)
+    }
+}
+
+/// Renders one template: SVG + params in, exported model bytes out.
+/// Clones share the template, so `replace_template` on one is seen by all.
+///
+/// ```
+/// use openscad_part_maker::{ExportFormat, PreviewOptions, Quality, RenderOptions, Renderer};
+/// use openscad_part_maker::scad_params::ScadParamTemplate;
+/// use std::path::Path;
+///
+/// let template = ScadParamTemplate::from_scad_text("WIDTH = 10; // @param\n").unwrap();
+/// let renderer = Renderer::new("openscad", template, RenderOptions::new("/app/part.scad"));
+///
+/// let mut params = renderer.template().instantiate();
+/// params.set_from_field("width", "25").unwrap();
+/// let args = renderer.command_args(
+///     Path::new("/tmp/work"),
+///     &params,
+///     ExportFormat::Stl,
+///     Quality::default(),
+///     &PreviewOptions::default(),
+/// );
+/// assert!(args.contains(&"WIDTH=25".to_string()));
+/// assert_eq!(args[args.len() - 2..], ["/tmp/work/output.stl", "/app/part.scad"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    openscad_bin: PathBuf,
+    template: Arc<RwLock<Arc<ScadParamTemplate>>>,
+    options: RenderOptions,
+}
+
+impl Renderer {
+    pub fn new(
+        openscad_bin: impl Into<PathBuf>,
+        template: ScadParamTemplate,
+        options: RenderOptions,
+    ) -> Self {
+        Self {
+            openscad_bin: openscad_bin.into(),
+            template: Arc::new(RwLock::new(Arc::new(template))),
+            options,
+        }
+    }
+
+    /// The current template; a render keeps the one it started with even
+    /// if `replace_template` runs meanwhile.
+    pub fn template(&self) -> Arc<ScadParamTemplate> {
+        self.template.read().unwrap().clone()
+    }
+
+    pub fn replace_template(&self, template: ScadParamTemplate) {
+        *self.template.write().unwrap() = Arc::new(template);
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut RenderOptions {
+        &mut self.options
+    }
+
+    /// The program actually spawned: the sandbox wrapper if there is one.
+    fn program(&self) -> PathBuf {
+        match self.options.sandbox_cmd.first() {
+            Some(wrapper) => PathBuf::from(wrapper),
+            None => self.openscad_bin.clone(),
+        }
+    }
+
+    /// The argv a render in `workdir` would run, for logging and debugging.
+    pub fn command_args(
+        &self,
+        workdir: &Path,
+        params: &ScadParams,
+        format: ExportFormat,
+        quality: Quality,
+        preview: &PreviewOptions,
+    ) -> Vec<String> {
+        let (svg_path, output_path) = work_paths(workdir, format);
+        let mut args = build_openscad_args(
+            quality.fs,
+            quality.fa,
+            quality.fn_,
+            params,
+            &svg_path,
+            &output_path,
+            &self.options.input_scad_path,
+        );
+        let features = self
+            .options
+            .features
+            .iter()
+            .flat_map(|name| ["--enable".to_string(), name.clone()]);
+        let flags = self.options.extra_args.iter().cloned().chain(features);
+        args.splice(1..1, flags.chain(preview.args(format)));
+        args
+    }
+
+    /// Render with the renderer's default quality.
+    pub async fn render(
+        &self,
+        svg: &[u8],
+        params: ScadParams,
+        format: ExportFormat,
+    ) -> Result<Vec<u8>, RenderError> {
+        self.render_with(svg, &params, format, self.options.quality)
+            .await
+    }
+
+    /// Render in a fresh temp dir with explicit quality settings.
+    pub async fn render_with(
+        &self,
+        svg: &[u8],
+        params: &ScadParams,
+        format: ExportFormat,
+        quality: Quality,
+    ) -> Result<Vec<u8>, RenderError> {
+        let tmpdir = tempfile::tempdir()?;
+        self.render_in(tmpdir.path(), svg, params, format, quality)
+            .await
+    }
+
+    /// Render using `workdir` for the SVG and output files (see `command_args`).
+    /// Transient failures are retried per `RenderOptions::retries`.
+    pub async fn render_in(
+        &self,
+        workdir: &Path,
+        svg: &[u8],
+        params: &ScadParams,
+        format: ExportFormat,
+        quality: Quality,
+    ) -> Result<Vec<u8>, RenderError> {
+        let rendered = self
+            .render_to_file(
+                workdir,
+                svg,
+                params,
+                format,
+                quality,
+                &PreviewOptions::default(),
+            )
+            .await?;
+        Ok(tokio::fs::read(&rendered.path).await?)
+    }
+
+    /// Like `render_in`, but leave the output in `workdir` and return its
+    /// path (and OpenSCAD's stderr), so large files can be streamed rather
+    /// than buffered. `preview` only affects PNG output.
+    pub async fn render_to_file(
+        &self,
+        workdir: &Path,
+        svg: &[u8],
+        params: &ScadParams,
+        format: ExportFormat,
+        quality: Quality,
+        preview: &PreviewOptions,
+    ) -> Result<RenderedFile, RenderError> {
+        guard_workdir(
+            workdir,
+            format,
+            &self.options.input_scad_path,
+            self.options.read_only_template,
+        )?;
+        if self.options.fake {
+            let (_, output_path) = work_paths(workdir, format);
+            tokio::fs::write(&output_path, fake_output(format)).await?;
+            return Ok(RenderedFile {
+                path: output_path,
+                stderr: String::new(),
+            });
+        }
+        with_retries(self.options.retries, RETRY_BACKOFF, || {
+            self.render_once(workdir, svg, params, format, quality, preview)
+        })
+        .await
+    }
+
+    async fn render_once(
+        &self,
+        workdir: &Path,
+        svg: &[u8],
+        params: &ScadParams,
+        format: ExportFormat,
+        quality: Quality,
+        preview: &PreviewOptions,
+    ) -> Result<RenderedFile, RenderError> {
+        let (svg_path, output_path) = work_paths(workdir, format);
+        tokio::fs::write(&svg_path, svg).await?;
+
+        let args = self.command_args(workdir, params, format, quality, preview);
+        let mut cmd = openscad_command(
+            &self.openscad_bin,
+            args,
+            &self.options,
+            workdir,
+            self.options.deterministic,
+        );
+        cmd.kill_on_drop(true);
+        let output = match self.options.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, cmd.output())
+                .await
+                .map_err(|_| RenderError::Timeout(timeout))?,
+            None => cmd.output().await,
+        }
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => RenderError::BinaryNotFound(self.program()),
+            _ => RenderError::Spawn(err),
+        })?;
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        debug!("openscad stderr:\n{stderr}");
+        let sigkilled = killed_by_sigkill(&output.status);
+        if let Some(limit_mib) = self.options.mem_limit_mib
+            && !output.status.success()
+            && looks_out_of_memory(sigkilled, &stderr)
+        {
+            return Err(RenderError::MemoryLimit { limit_mib });
+        }
+        if !output.status.success() {
+            return Err(RenderError::NonZeroExit {
+                code: output.status.code(),
+                sigkilled,
+                stderr,
+            });
+        }
+
+        let len = tokio::fs::metadata(&output_path)
+            .await
+            .map_err(|err| match err.kind() {
+                ErrorKind::NotFound => RenderError::OutputMissing(output_path.clone()),
+                _ => RenderError::Io(err),
+            })?
+            .len() as usize;
+        // Even an empty binary STL has a full header; anything smaller (an
+        // empty file, or an ASCII `solid`/`endsolid` pair) has no triangles.
+        if format == ExportFormat::Stl && len <= stl::BINARY_HEADER_LEN {
+            return Err(RenderError::EmptyGeometry { len });
+        }
+        if self.options.deterministic && format == ExportFormat::Stl {
+            let bytes = tokio::fs::read(&output_path).await?;
+            tokio::fs::write(&output_path, stl::canonicalize(&bytes)).await?;
+        }
+        Ok(RenderedFile {
+            path: output_path,
+            stderr,
+        })
+    }
+
+    /// Render once with default params and a placeholder SVG, failing if
+    /// OpenSCAD errors or exceeds `timeout`. A missing OpenSCAD binary only
+    /// logs a warning so the server can still start in dev.
+    pub async fn preflight(&self, timeout: Duration) -> anyhow::Result<()> {
+        if self.options.fake {
+            info!("Preflight skipped: fake rendering is active");
+            return Ok(());
+        }
+        let input_scad_path = &self.options.input_scad_path;
+        let tmpdir = tempfile::tempdir().context("create preflight temp dir")?;
+        let svg_path = tmpdir.path().join("placeholder.svg");
+        let out_path = tmpdir.path().join("preflight.stl");
+        std::fs::write(&svg_path, PLACEHOLDER_SVG).context("write placeholder SVG")?;
+
+        let args = build_preflight_args(&self.template(), &svg_path, &out_path, input_scad_path);
+        let mut cmd = openscad_command(
+            &self.openscad_bin,
+            args,
+            &self.options,
+            tmpdir.path(),
+            false,
+        );
+        cmd.kill_on_drop(true);
+
+        info!("Preflight: rendering {}...", input_scad_path.display());
+        let status = match tokio::time::timeout(timeout, cmd.status()).await {
+            Err(_) => bail!("preflight render timed out after {timeout:?}"),
+            Ok(Err(err)) if err.kind() == ErrorKind::NotFound => {
+                warn!("Preflight skipped: openscad binary not found");
+                return Ok(());
+            }
+            Ok(result) => result.context("spawn openscad for preflight")?,
+        };
+        if !status.success() {
+            bail!(
+                "preflight render of {} failed: openscad exited with {status}",
+                input_scad_path.display()
+            );
+        }
+        info!("Preflight passed");
+        Ok(())
+    }
+}
+
+/// A successful `Renderer::render_to_file`.
+#[derive(Debug, Clone)]
+pub struct RenderedFile {
+    /// The exported file, inside the render's workdir.
+    pub path: PathBuf,
+    /// What OpenSCAD printed; see `echo_lines`.
+    pub stderr: String,
+}
+
+/// The `echo()` output in OpenSCAD's stderr: each `ECHO: ` line, without the
+/// prefix (e.g. `"width", 42` for `echo("width", 42)`).
+pub fn echo_lines(stderr: &str) -> Vec<String> {
+    stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("ECHO: "))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Where a render in `workdir` reads its SVG and writes its output.
+fn work_paths(workdir: &Path, format: ExportFormat) -> (PathBuf, PathBuf) {
+    (
+        workdir.join("input.svg"),
+        workdir.join(format!("output.{}", format.extension())),
+    )
+}
+
+/// Check that a render in `workdir` can't write over the template: once
+/// symlinks are resolved, neither its SVG nor its output path may be
+/// `input_scad_path`, and with `read_only_template`, `workdir` must not be
+/// inside the template's directory at all.
+fn guard_workdir(
+    workdir: &Path,
+    format: ExportFormat,
+    input_scad_path: &Path,
+    read_only_template: bool,
+) -> Result<(), RenderError> {
+    let refuse = |msg: String| {
+        Err(RenderError::Io(std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            msg,
+        )))
+    };
+    let resolve = |p: &Path| {
+        p.canonicalize()
+            .or_else(|_| std::path::absolute(p))
+            .unwrap_or_else(|_| p.to_path_buf())
+    };
+    let workdir = resolve(workdir);
+    let input_scad_path = resolve(input_scad_path);
+    let (svg_path, output_path) = work_paths(&workdir, format);
+    for path in [&svg_path, &output_path] {
+        if *path == input_scad_path {
+            return refuse(format!("{} would overwrite the template", path.display()));
+        }
+    }
+    if read_only_template {
+        let template_dir = input_scad_path.parent().unwrap_or(Path::new("/"));
+        if workdir.starts_with(template_dir) {
+            return refuse(format!(
+                "render dir {} is inside the template dir {}",
+                workdir.display(),
+                template_dir.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Assemble the OpenSCAD argument vector for one render.
+pub fn build_openscad_args(
+    fs: f32,
+    fa: f32,
+    fn_: i32,
+    scad_params: &ScadParams,
+    svg_path: &Path,
+    output_path: &Path,
+    input_scad_path: &Path,
+) -> Vec<String> {
+    let mut args = Vec::new();
+
+    args.push("--render".into());
+    args.push("-D".into());
+    args.push(format!("fs={fs}"));
+    args.push("-D".into());
+    args.push(format!("fa={fa}"));
+    args.push("-D".into());
+    args.push(format!("fn={fn_}"));
+
+    for define in scad_params.iter_defines() {
+        args.push("-D".into());
+        args.push(define);
+    }
+
+    args.push("-D".into());
+    args.push(format!("SVG_PATH=\"{}\"", svg_path.display()));
+
+    args.push("-o".into());
+    args.push(output_path.to_string_lossy().to_string());
+    args.push(input_scad_path.to_string_lossy().to_string());
+
+    args
+}
+
+/// `OPENSCADPATH` for the child: `lib_paths`, then whatever it would have
+/// had without them (from `env`, else our own environment). `None` without
+/// lib paths, or if one can't be joined (it holds the path separator).
+fn openscad_path(lib_paths: &[PathBuf], env: &[(String, String)]) -> Option<std::ffi::OsString> {
+    if lib_paths.is_empty() {
+        return None;
+    }
+    let inherited = env
+        .iter()
+        .rev()
+        .find(|(key, _)| key == "OPENSCADPATH")
+        .map(|(_, value)| value.into())
+        .or_else(|| std::env::var_os("OPENSCADPATH"))
+        .unwrap_or_default();
+    let paths = lib_paths
+        .iter()
+        .cloned()
+        .chain(std::env::split_paths(&inherited));
+    std::env::join_paths(paths).ok()
+}
+
+/// Executable used when no `--openscad-bin` is given; resolved via `PATH`.
+pub const DEFAULT_OPENSCAD_BIN: &str = "openscad";
+
+/// Build the OpenSCAD child command for `args`, run in `workdir` under the
+/// sandbox wrapper and with the environment, thread count and priority from
+/// `options`. Deterministic settings win over `options.env` and
+/// `options.threads`.
+pub fn openscad_command(
+    openscad_bin: &Path,
+    args: Vec<String>,
+    options: &RenderOptions,
+    workdir: &Path,
+    deterministic: bool,
+) -> Command {
+    let mut argv = sandbox_argv(&options.sandbox_cmd, workdir, &options.input_scad_path);
+    argv.push(openscad_bin.to_string_lossy().into_owned());
+    argv.extend(args);
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    cmd.envs(options.env.iter().map(|(k, v)| (k, v)));
+    if let Some(path) = openscad_path(&options.lib_paths, &options.env) {
+        cmd.env("OPENSCADPATH", path);
+    }
+    if let Some(threads) = options.threads {
+        cmd.env("OPENSCAD_THREADS", threads.to_string());
+    }
+    if deterministic {
+        apply_deterministic_env(&mut cmd);
+    }
+    #[cfg(unix)]
+    if let Some(nice) = options.nice {
+        set_child_priority(&mut cmd, nice);
+    }
+    #[cfg(unix)]
+    if let Some(mib) = options.mem_limit_mib {
+        set_child_mem_limit(&mut cmd, mib);
+    }
+    cmd
+}
+
+/// Whether a failed OpenSCAD run looks like it hit the memory limit: an
+/// allocation failure in its output (CGAL aborts on `std::bad_alloc`), or
+/// `sigkilled`, as by the kernel's OOM killer. Other signals, such as a
+/// SIGSEGV, are ordinary crashes.
+fn looks_out_of_memory(sigkilled: bool, stderr: &str) -> bool {
+    sigkilled
+        || ["bad_alloc", "out of memory", "Cannot allocate memory"]
+            .iter()
+            .any(|needle| stderr.contains(needle))
+}
+
+/// Whether the process was killed with SIGKILL.
+#[cfg(unix)]
+fn killed_by_sigkill(status: &std::process::ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal() == Some(libc::SIGKILL)
+}
+
+#[cfg(not(unix))]
+fn killed_by_sigkill(_status: &std::process::ExitStatus) -> bool {
+    false
+}
+
+/// The `--sandbox-cmd` wrapper with `{workdir}` (the render's temp dir, the
+/// only place OpenSCAD needs to write) and `{template_dir}` (the directory
+/// of the input `.scad`, to bind read-only) filled in as absolute paths.
+/// OpenSCAD's path and arguments follow it.
+pub fn sandbox_argv(sandbox_cmd: &[String], workdir: &Path, input_scad_path: &Path) -> Vec<String> {
+    let absolute = |p: &Path| {
+        std::path::absolute(p)
+            .unwrap_or_else(|_| p.to_path_buf())
+            .to_string_lossy()
+            .into_owned()
+    };
+    let workdir = absolute(workdir);
+    let template_dir = input_scad_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let template_dir = absolute(template_dir);
+    sandbox_cmd
+        .iter()
+        .map(|arg| {
+            arg.replace("{workdir}", &workdir)
+                .replace("{template_dir}", &template_dir)
+        })
+        .collect()
+}
+
+/// Set the child's niceness before it execs, so long renders don't starve
+/// the server. Going below the server's own niceness needs privileges.
+#[cfg(unix)]
+fn set_child_priority(cmd: &mut Command, nice: i32) {
+    // SAFETY: the closure runs in the forked child before exec and only calls
+    // `setpriority`, which is async-signal-safe.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// `RLIMIT_AS` for a limit of `mib` MiB (soft and hard, so the child can't
+/// raise it again).
+#[cfg(unix)]
+fn mem_rlimit(mib: u64) -> libc::rlimit {
+    let bytes = mib.saturating_mul(1024 * 1024);
+    let bytes = libc::rlim_t::try_from(bytes).unwrap_or(libc::RLIM_INFINITY);
+    libc::rlimit {
+        rlim_cur: bytes,
+        rlim_max: bytes,
+    }
+}
+
+/// Cap the child's address space before it execs, so a runaway render fails
+/// its own allocations instead of pushing the host into the OOM killer.
+#[cfg(unix)]
+fn set_child_mem_limit(cmd: &mut Command, mib: u64) {
+    let limit = mem_rlimit(mib);
+    // SAFETY: the closure runs in the forked child before exec and only calls
+    // `setrlimit`, which is async-signal-safe.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Arguments for a preflight render: template defaults, coarse quality.
+pub fn build_preflight_args(
+    template: &ScadParamTemplate,
+    svg_path: &Path,
+    output_path: &Path,
+    input_scad_path: &Path,
+) -> Vec<String> {
+    build_openscad_args(
+        1.0,
+        30.0,
+        PREFLIGHT_FN,
+        &template.instantiate(),
+        svg_path,
+        output_path,
+        input_scad_path,
+    )
+}
+
+/// Pin down the sources of run-to-run variation in the OpenSCAD child.
+///
+/// Parallel geometry evaluation may emit triangles in a different order each
+/// run, so we force a single thread; this makes large renders noticeably
+/// slower. The seed/epoch variables are only honoured by wrappers and builds
+/// that look for them (stock OpenSCAD seeds `rands()` from the clock), which
+/// is why the output is also canonicalized afterwards (see `stl::canonicalize`).
+pub fn apply_deterministic_env(cmd: &mut Command) {
+    cmd.env("OPENSCAD_THREADS", "1");
+    cmd.env("OPENSCAD_RANDOM_SEED", "0");
+    cmd.env("SOURCE_DATE_EPOCH", "0");
+}
+
+/// A standalone `.scad` reproducing a render: the main file's source followed
+/// by the `-D` defines from `args` as plain assignments. OpenSCAD applies
+/// `-D` after the file, so appending them keeps the same precedence.
+pub fn reproduction_scad(main_source: &str, args: &[String]) -> String {
+    let mut out = main_source.trim_end().to_string();
+    out.push_str("\n\n// ---- -D defines from the render request ----\n");
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-D"
+            && let Some(define) = args.next()
+        {
+            out.push_str(define);
+            out.push_str(";\n");
+        }
+    }
+    out
+}
+
+/// Stand-in output for `RenderOptions::fake`: a single-triangle binary STL,
+/// or a placeholder drawing or image for the other formats.
+pub fn fake_output(format: ExportFormat) -> Vec<u8> {
+    match format {
+        ExportFormat::Stl => stl::single_triangle(),
+        ExportFormat::Svg => PLACEHOLDER_SVG.as_bytes().to_vec(),
+        ExportFormat::Dxf => PLACEHOLDER_DXF.as_bytes().to_vec(),
+        ExportFormat::Png => PLACEHOLDER_PNG.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scad_params::ScadParamTemplate;
+    use std::path::PathBuf;
+
+    #[test]
+    fn build_openscad_args_contains_expected_params_and_order() {
+        let scad = r#"
+NAME="output"; // @param
+MODE="base"; // @param
+SHAPE="octagon"; // @param
+INTERLOCK=false; // @param
+USE_SPINNER=true; // @param
+"#;
+
+        let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+
+        let mut p = tmpl.instantiate();
+        p.set_from_field("name", "My Logo").unwrap();
+        p.set_from_field("mode", "preview").unwrap();
+        p.set_from_field("shape", "circle").unwrap();
+        p.set_from_field("interlock", "true").unwrap();
+        p.set_from_field("use_spinner", "false").unwrap();
+
+        let svg = PathBuf::from("/tmp/input.svg");
+        let stl = PathBuf::from("/tmp/output.stl");
+        let main_scad = PathBuf::from("/app/input.scad");
+
+        let args = build_openscad_args(0.25, 9.0, 123, &p, &svg, &stl, &main_scad);
+
+        assert_eq!(args[0], "--render");
+        assert_eq!(args[1], "-D");
+        assert_eq!(args[2], "fs=0.25");
+        assert!(args.contains(&"NAME=\"My Logo\"".to_string()));
+        assert!(args.contains(&"MODE=\"preview\"".to_string()));
+        assert!(args.contains(&"SHAPE=\"circle\"".to_string()));
+        assert!(args.contains(&"INTERLOCK=true".to_string()));
+        assert!(args.contains(&"USE_SPINNER=false".to_string()));
+    }
+
+    #[test]
+    fn reproduction_scad_appends_defines() {
+        let args: Vec<String> = "--render -D fn=64 -D COASTER_D=90 -o out.stl"
+            .split(' ')
+            .map(String::from)
+            .collect();
+        let scad = reproduction_scad("COASTER_D = 101.6;\ncylinder(d=COASTER_D);\n", &args);
+        assert!(scad.starts_with("COASTER_D = 101.6;\ncylinder(d=COASTER_D);\n"));
+        assert!(scad.ends_with("fn=64;\nCOASTER_D=90;\n"));
+        assert!(!scad.contains("out.stl"));
+    }
+
+    #[test]
+    fn build_preflight_args_uses_defaults_and_coarse_quality() {
+        let scad = r#"
+MODE="base"; // @param
+COASTER_D=101.6; // @param
+"#;
+        let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+        let args = build_preflight_args(
+            &tmpl,
+            Path::new("/tmp/placeholder.svg"),
+            Path::new("/tmp/preflight.stl"),
+            Path::new("/app/input.scad"),
+        );
+
+        assert_eq!(args[0], "--render");
+        assert!(args.contains(&format!("fn={PREFLIGHT_FN}")));
+        assert!(args.contains(&"MODE=\"base\"".to_string()));
+        assert!(args.contains(&"COASTER_D=101.6".to_string()));
+        assert!(args.contains(&"SVG_PATH=\"/tmp/placeholder.svg\"".to_string()));
+        assert_eq!(
+            &args[args.len() - 3..],
+            ["-o", "/tmp/preflight.stl", "/app/input.scad"]
+        );
+    }
+
+    #[test]
+    fn renderer_command_args_use_workdir_and_quality() {
+        let tmpl = ScadParamTemplate::from_scad_text("MODE=\"base\"; // @param\n").unwrap();
+        let mut options = RenderOptions::new("/app/input.scad");
+        options.quality.fn_ = 64;
+        let renderer = Renderer::new("/usr/bin/openscad", tmpl, options);
+
+        let params = renderer.template().instantiate();
+        let quality = renderer.options().quality;
+        let args = renderer.command_args(
+            Path::new("/work"),
+            &params,
+            ExportFormat::Dxf,
+            quality,
+            &PreviewOptions::default(),
+        );
+
+        assert!(args.contains(&"fn=64".to_string()));
+        assert!(args.contains(&"MODE=\"base\"".to_string()));
+        assert!(args.contains(&"SVG_PATH=\"/work/input.svg\"".to_string()));
+        assert_eq!(
+            &args[args.len() - 3..],
+            ["-o", "/work/output.dxf", "/app/input.scad"]
+        );
+    }
+
+    #[test]
+    fn parse_openscad_version_reads_release() {
+        assert_eq!(
+            parse_openscad_version("OpenSCAD version 2021.01\n"),
+            Some((2021, 1))
+        );
+        assert_eq!(
+            parse_openscad_version("OpenSCAD version 2024.12.06.ai23456"),
+            Some((2024, 12))
+        );
+        assert_eq!(parse_openscad_version("command not found"), None);
+    }
+
+    #[test]
+    fn strict_args_depend_on_version() {
+        assert_eq!(strict_args(Some((2019, 5))), ["--hardwarnings"]);
+        assert_eq!(strict_args(None), ["--hardwarnings"]);
+        let args = strict_args(Some((2021, 1)));
+        assert!(args.contains(&"--check-parameters".to_string()));
+        assert!(args.contains(&"--check-parameter-ranges".to_string()));
+    }
+
+    #[test]
+    fn strict_flags_appear_in_command_args() {
+        let tmpl = ScadParamTemplate::from_scad_text("MODE=\"base\"; // @param\n").unwrap();
+        let mut options = RenderOptions::new("/app/input.scad");
+        options.extra_args = strict_args(Some((2021, 1)));
+        let renderer = Renderer::new("openscad", tmpl, options);
+        let args = renderer.command_args(
+            Path::new("/work"),
+            &renderer.template().instantiate(),
+            ExportFormat::Stl,
+            Quality::default(),
+            &PreviewOptions::default(),
+        );
+        assert_eq!(
+            args[..6],
+            [
+                "--render",
+                "--hardwarnings",
+                "--check-parameters",
+                "true",
+                "--check-parameter-ranges",
+                "true"
+            ]
+        );
+        assert_eq!(args[args.len() - 1], "/app/input.scad");
+    }
+
+    #[test]
+    fn enabled_features_are_passed_as_pairs() {
+        let tmpl = ScadParamTemplate::from_scad_text("MODE=\"base\"; // @param\n").unwrap();
+        let mut options = RenderOptions::new("/app/input.scad");
+        options.features = vec!["lazy-union".to_string(), "roof".to_string()];
+        let renderer = Renderer::new("openscad", tmpl, options);
+        let args = renderer.command_args(
+            Path::new("/work"),
+            &renderer.template().instantiate(),
+            ExportFormat::Stl,
+            Quality::default(),
+            &PreviewOptions::default(),
+        );
+        assert_eq!(
+            args[..5],
+            ["--render", "--enable", "lazy-union", "--enable", "roof"]
+        );
+    }
+
+    #[test]
+    fn feature_names_are_checked() {
+        assert_eq!(check_feature("lazy-union"), Ok(true));
+        assert_eq!(check_feature("some-future-thing"), Ok(false));
+        assert!(check_feature("").is_err());
+        assert!(check_feature("--hardwarnings").is_err());
+        assert!(check_feature("roof manifold").is_err());
+    }
+
+    #[test]
+    fn colorscheme_is_passed_for_png_only() {
+        let tmpl = ScadParamTemplate::from_scad_text("MODE=\"base\"; // @param\n").unwrap();
+        let renderer = Renderer::new("openscad", tmpl, RenderOptions::new("/app/input.scad"));
+        let preview = PreviewOptions {
+            colorscheme: Some("Tomorrow Night"),
+            ..PreviewOptions::default()
+        };
+        let args = |format| {
+            renderer.command_args(
+                Path::new("/work"),
+                &renderer.template().instantiate(),
+                format,
+                Quality::default(),
+                &preview,
+            )
+        };
+        assert_eq!(
+            args(ExportFormat::Png)[..3],
+            ["--render", "--colorscheme", "Tomorrow Night"]
+        );
+        assert!(!args(ExportFormat::Stl).contains(&"--colorscheme".to_string()));
+    }
+
+    #[test]
+    fn camera_auto_frames_the_part_and_explicit_passes_through() {
+        let args = |camera: &str| {
+            PreviewOptions {
+                camera: Some(camera.parse().unwrap()),
+                ..PreviewOptions::default()
+            }
+            .args(ExportFormat::Png)
+        };
+        assert_eq!(args("auto"), ["--viewall", "--autocenter"]);
+        assert_eq!(args("0,0,0,55,0,25,140"), ["--camera=0,0,0,55,0,25,140"]);
+        assert_eq!(args("0, 0, 0, 55, 0, 25"), ["--camera=0, 0, 0, 55, 0, 25"]);
+        assert!("1,2,3".parse::<Camera>().is_err());
+        assert!("0,0,0,a,0,25".parse::<Camera>().is_err());
+    }
+
+    #[test]
+    fn echo_lines_are_parsed_from_stderr() {
+        let stderr = "Parsing design (AST generation)...\n\
+            ECHO: \"width\", 101.6\n\
+            WARNING: Ignoring unknown variable 'x'\n\
+            ECHO: \"holes = \", [1, 2]\n\
+            Top level object is a 3D object:\n   Facets:         42\n";
+        assert_eq!(
+            echo_lines(stderr),
+            ["\"width\", 101.6", "\"holes = \", [1, 2]"]
+        );
+        assert!(echo_lines("Rendering Polygon Mesh using CGAL...\n").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mem_limit_is_applied_to_the_child() {
+        assert_eq!(mem_rlimit(512).rlim_cur, 512 * 1024 * 1024);
+        assert_eq!(mem_rlimit(512).rlim_max, 512 * 1024 * 1024);
+
+        // `ulimit -v` reports RLIMIT_AS in KiB.
+        let mut options = RenderOptions::new("in.scad");
+        options.mem_limit_mib = Some(512);
+        let output = openscad_command(
+            Path::new("sh"),
+            vec!["-c".into(), "ulimit -v".into()],
+            &options,
+            Path::new("."),
+            false,
+        )
+        .as_std_mut()
+        .output()
+        .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "524288");
+    }
+
+    #[test]
+    fn out_of_memory_failures_are_recognized() {
+        let abort = "terminate called after throwing an instance of 'std::bad_alloc'\n";
+        assert!(looks_out_of_memory(false, abort));
+        assert!(looks_out_of_memory(true, ""));
+        let parse_error = "ERROR: Parser error in line 3\n";
+        assert!(!looks_out_of_memory(false, parse_error));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_sigkill_counts_as_out_of_memory() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Wait statuses: the low bits hold the terminating signal.
+        let status = std::process::ExitStatus::from_raw;
+        assert!(killed_by_sigkill(&status(libc::SIGKILL)));
+        assert!(!killed_by_sigkill(&status(libc::SIGSEGV)));
+        assert!(!killed_by_sigkill(&status(libc::SIGTERM)));
+        assert!(!killed_by_sigkill(&status(1 << 8)));
+    }
+
+    #[test]
+    fn work_paths_stay_in_the_render_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        for format in [ExportFormat::Stl, ExportFormat::Svg, ExportFormat::Png] {
+            let (svg_path, output_path) = work_paths(tmp.path(), format);
+            assert!(svg_path.starts_with(tmp.path()));
+            assert!(output_path.starts_with(tmp.path()));
+        }
+
+        let template_dir = tmp.path().join("template");
+        let workdir = template_dir.join("work");
+        std::fs::create_dir_all(&workdir).unwrap();
+        let input = template_dir.join("part.scad");
+        std::fs::write(&input, "").unwrap();
+        let other = tempfile::tempdir().unwrap();
+        assert!(guard_workdir(&workdir, ExportFormat::Stl, &input, false).is_ok());
+        assert!(guard_workdir(other.path(), ExportFormat::Stl, &input, true).is_ok());
+        let err = guard_workdir(&workdir, ExportFormat::Stl, &input, true).unwrap_err();
+        assert!(err.to_string().contains("inside the template dir"));
+
+        // A symlink doesn't hide that the render dir is the template's.
+        #[cfg(unix)]
+        {
+            let link = other.path().join("link");
+            std::os::unix::fs::symlink(&workdir, &link).unwrap();
+            assert!(guard_workdir(&link, ExportFormat::Stl, &input, true).is_err());
+        }
+
+        // Nor may an output path be the template itself.
+        let named_like_output = workdir.join("output.stl");
+        std::fs::write(&named_like_output, "").unwrap();
+        let err =
+            guard_workdir(&workdir, ExportFormat::Stl, &named_like_output, false).unwrap_err();
+        assert!(err.to_string().contains("would overwrite the template"));
+        assert!(guard_workdir(&workdir, ExportFormat::Svg, &named_like_output, false).is_ok());
+    }
+
+    #[test]
+    fn transient_errors_are_classified() {
+        let exit = |code, sigkilled| RenderError::NonZeroExit {
+            code,
+            sigkilled,
+            stderr: String::new(),
+        };
+        assert!(RenderError::Spawn(std::io::Error::other("EAGAIN")).is_transient());
+        assert!(exit(None, true).is_transient());
+        assert!(exit(Some(137), false).is_transient());
+        // A crash on bad geometry (SIGSEGV) or a CGAL error repeats on retry.
+        assert!(!exit(None, false).is_transient());
+        assert!(!exit(Some(1), false).is_transient());
+        assert!(!RenderError::Io(std::io::Error::other("disk full")).is_transient());
+        assert!(!RenderError::Timeout(Duration::from_secs(1)).is_transient());
+    }
+
+    #[tokio::test]
+    async fn with_retries_retries_transient_failure_once() {
+        let calls = std::cell::Cell::new(0);
+        let result = with_retries(2, Duration::from_millis(1), || {
+            calls.set(calls.get() + 1);
+            let n = calls.get();
+            async move {
+                if n == 1 {
+                    Err(RenderError::Spawn(std::io::Error::other("transient")))
+                } else {
+                    Ok(n)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn with_retries_gives_up_on_bad_geometry() {
+        let calls = std::cell::Cell::new(0);
+        let result: Result<(), _> = with_retries(3, Duration::from_millis(1), || {
+            calls.set(calls.get() + 1);
+            async {
+                Err(RenderError::NonZeroExit {
+                    code: Some(1),
+                    sigkilled: false,
+                    stderr: String::new(),
+                })
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn with_retries_gives_up_on_a_segfault() {
+        let calls = std::cell::Cell::new(0);
+        let result: Result<(), _> = with_retries(3, Duration::from_millis(1), || {
+            calls.set(calls.get() + 1);
+            async {
+                Err(RenderError::NonZeroExit {
+                    code: None,
+                    sigkilled: false,
+                    stderr: "Segmentation fault".to_string(),
+                })
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn with_retries_stops_after_limit() {
+        let calls = std::cell::Cell::new(0);
+        let result: Result<(), _> = with_retries(2, Duration::from_millis(1), || {
+            calls.set(calls.get() + 1);
+            async { Err(RenderError::Spawn(std::io::Error::other("still down"))) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn env_file_vars_reach_the_child_command() {
+        let mut options = RenderOptions::new("in.scad");
+        options.env = vec![
+            ("OPENSCADPATH".to_string(), "/opt/libs".to_string()),
+            ("OPENSCAD_THREADS".to_string(), "8".to_string()),
+        ];
+        let envs = |options: &RenderOptions, deterministic| {
+            let cmd = openscad_command(
+                Path::new("openscad"),
+                Vec::new(),
+                options,
+                Path::new("/tmp"),
+                deterministic,
+            );
+            cmd.as_std()
+                .get_envs()
+                .map(|(k, v)| (k.to_owned(), v.map(|v| v.to_owned())))
+                .collect::<std::collections::HashMap<_, _>>()
+        };
+        let value = |s: &str| Some(std::ffi::OsString::from(s));
+        let key = std::ffi::OsStr::new;
+
+        let plain = envs(&options, false);
+        assert_eq!(plain[key("OPENSCADPATH")], value("/opt/libs"));
+        assert_eq!(plain[key("OPENSCAD_THREADS")], value("8"));
+        // --deterministic still pins threads.
+        assert_eq!(envs(&options, true)[key("OPENSCAD_THREADS")], value("1"));
+
+        // --render-threads beats the env file.
+        options.threads = Some(2);
+        assert_eq!(envs(&options, false)[key("OPENSCAD_THREADS")], value("2"));
+        options.env.clear();
+        assert_eq!(envs(&options, false)[key("OPENSCAD_THREADS")], value("2"));
+    }
+
+    #[test]
+    fn lib_paths_come_first_in_openscadpath() {
+        let mut options = RenderOptions::new("in.scad");
+        options.lib_paths = vec![PathBuf::from("/srv/libs"), PathBuf::from("/opt/BOSL2")];
+        options.env = vec![("OPENSCADPATH".to_string(), "/opt/libs".to_string())];
+        let openscadpath = |options: &RenderOptions| {
+            let cmd = openscad_command(
+                Path::new("openscad"),
+                Vec::new(),
+                options,
+                Path::new("/tmp"),
+                false,
+            );
+            cmd.as_std()
+                .get_envs()
+                .find(|(k, _)| *k == "OPENSCADPATH")
+                .and_then(|(_, v)| v.map(|v| v.to_owned()))
+        };
+        let joined = std::env::join_paths(["/srv/libs", "/opt/BOSL2", "/opt/libs"]).unwrap();
+        assert_eq!(openscadpath(&options), Some(joined));
+
+        // Without lib paths the env file's value passes through untouched.
+        options.lib_paths.clear();
+        assert_eq!(openscadpath(&options), Some("/opt/libs".into()));
+    }
+
+    #[test]
+    fn sandbox_cmd_is_prepended_to_the_openscad_argv() {
+        let mut options = RenderOptions::new("/srv/templates/coaster.scad");
+        let args = vec!["-o".to_string(), "out.stl".to_string()];
+        let argv = |options: &RenderOptions| {
+            let cmd = openscad_command(
+                Path::new("/usr/bin/openscad"),
+                args.clone(),
+                options,
+                Path::new("/tmp/render-1"),
+                false,
+            );
+            let std = cmd.as_std();
+            std::iter::once(std.get_program())
+                .chain(std.get_args())
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(argv(&options), ["/usr/bin/openscad", "-o", "out.stl"]);
+
+        options.sandbox_cmd = shell_words::split(
+            "bwrap --ro-bind /usr /usr --ro-bind {template_dir} {template_dir} --bind {workdir} {workdir} --",
+        )
+        .unwrap();
+        assert_eq!(
+            argv(&options),
+            [
+                "bwrap",
+                "--ro-bind",
+                "/usr",
+                "/usr",
+                "--ro-bind",
+                "/srv/templates",
+                "/srv/templates",
+                "--bind",
+                "/tmp/render-1",
+                "/tmp/render-1",
+                "--",
+                "/usr/bin/openscad",
+                "-o",
+                "out.stl",
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn render_nice_lowers_child_priority() {
+        let mut options = RenderOptions::new("in.scad");
+        options.nice = Some(19);
+        let args = vec!["-c".to_string(), "nice".to_string()];
+        let output = openscad_command(Path::new("sh"), args, &options, Path::new("/tmp"), false)
+            .output()
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "19");
+    }
+
+    #[test]
+    fn deterministic_env_pins_threads() {
+        let mut cmd = Command::new("openscad");
+        apply_deterministic_env(&mut cmd);
+        let envs: Vec<_> = cmd.as_std().get_envs().collect();
+        assert!(envs.contains(&(
+            std::ffi::OsStr::new("OPENSCAD_THREADS"),
+            Some(std::ffi::OsStr::new("1"))
+        )));
+    }
+}
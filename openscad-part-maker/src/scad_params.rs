@@ -1,20 +1,32 @@
+use crate::format::{ExportFormat, parse_colorscheme};
 use anyhow::Context;
+use log::warn;
 use regex::Regex;
+use serde::Serialize;
 use std::{
-    collections::{BTreeMap, HashSet},
-    fs,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs,
     path::{Path, PathBuf},
+    sync::{
+        LazyLock, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ParamType {
     Number,
+    /// A number whose default is a whole literal (e.g. `SEG = 12;`).
+    Integer,
     Bool,
     String,
+    /// `// @param type: file`: a path the server points at an uploaded file.
+    File,
 }
 
 /// A parameter discovered from .scad defaults.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ParamSpec {
     pub name: String,    // e.g. "COASTER_D"
     pub default: String, // RHS text as OpenSCAD syntax, e.g. "101.6" or "\"octagon\""
@@ -24,6 +36,30 @@ pub struct ParamSpec {
     #[allow(dead_code)]
     pub comment: String,
     pub options: Vec<String>,
+    pub advanced: bool, // `// @param advanced`: tucked away in the form
+    /// `// @param default: 90`: what the form is pre-filled with, in the same
+    /// OpenSCAD syntax as `default`. Only the form uses it; a request that
+    /// leaves the field out still renders with `ScadParamTemplate::defaults`.
+    pub form_default: Option<String>,
+    /// `// @param step 0.5`: the form's number-input increment.
+    pub step: Option<f64>,
+    /// `// @param enforce-step`: reject submitted numbers off the `step` grid.
+    pub enforce_step: bool,
+    /// What a derived default like `COASTER_D/2` works out to, from the
+    /// params above it (see `expr`). Display only: renders still pass the
+    /// expression to OpenSCAD.
+    pub computed: Option<f64>,
+    /// A `// @param` whose default is an expression that can't be worked
+    /// out, so there is no usable baseline: requests must supply a value.
+    pub required: bool,
+}
+
+impl ParamSpec {
+    /// Value to pre-fill the form with: `form_default` if the comment sets
+    /// one, otherwise the SCAD default.
+    pub fn form_value(&self) -> &str {
+        self.form_default.as_deref().unwrap_or(&self.default)
+    }
 }
 
 /// Template/specs discovered from the input .scad tree.
@@ -32,16 +68,92 @@ pub struct ParamSpec {
 pub struct ScadParamTemplate {
     pub specs: BTreeMap<String, ParamSpec>,
     pub defaults: BTreeMap<String, String>,
+    /// `// @mode-formats MODE: fmt|fmt` declarations, keyed by MODE value.
+    pub mode_formats: BTreeMap<String, Vec<ExportFormat>>,
+    /// `// @colorscheme NAME`: default PNG colorscheme for previews.
+    pub colorscheme: Option<&'static str>,
+    /// Prefix on form field names (e.g. `scad_` for `scad_coaster_d`).
+    pub field_prefix: String,
+    /// Make `set_from_field` reject fields that match no param.
+    pub reject_unknown: bool,
+    /// The param the template assigns `$fn` from (`$fn = SEG;`). Its value
+    /// decides the segment count, so the `fn` quality field has no effect.
+    pub fn_binding: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ScadParams {
     pub specs: BTreeMap<String, ParamSpec>,
     pub values: BTreeMap<String, String>,
+    pub field_prefix: String,
+    pub reject_unknown: bool,
 }
 
+/// Why a submitted value was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamErrorKind {
+    BadNumber,
+    BadInteger,
+    BadBool,
+    OutOfRange { max: f64 },
+    OffStep { step: f64 },
+    Missing,
+    InvalidOption { options: Vec<String> },
+    Unknown,
+    UploadExpected,
+}
+
+/// A form field whose value failed validation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamError {
+    pub field: String,
+    pub value: String,
+    pub kind: ParamErrorKind,
+}
+
+impl ParamError {
+    pub fn new(field: &str, value: &str, kind: ParamErrorKind) -> Self {
+        Self {
+            field: field.to_string(),
+            value: value.to_string(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (field, value) = (&self.field, &self.value);
+        match &self.kind {
+            ParamErrorKind::BadNumber => write!(f, "{field}: expected a number, got '{value}'"),
+            ParamErrorKind::BadInteger => {
+                write!(f, "{field}: expected a whole number, got '{value}'")
+            }
+            ParamErrorKind::BadBool => write!(f, "{field}: expected true/false, got '{value}'"),
+            ParamErrorKind::OutOfRange { max } => {
+                write!(f, "{field}: {value} is out of range (maximum {max})")
+            }
+            ParamErrorKind::OffStep { step } => {
+                write!(f, "{field}: {value} is not a multiple of {step}")
+            }
+            ParamErrorKind::InvalidOption { options } => write!(
+                f,
+                "{field}: '{value}' is not a valid option (expected one of: {})",
+                options.join(", ")
+            ),
+            ParamErrorKind::Unknown => write!(f, "{field}: no such parameter"),
+            ParamErrorKind::Missing => {
+                write!(f, "{field}: required (the template has no usable default)")
+            }
+            ParamErrorKind::UploadExpected => write!(f, "{field}: expected a file upload"),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
 /// Parse common bool variants from HTML forms.
-pub fn parse_bool(value: &str) -> Result<bool, ()> {
+pub(crate) fn parse_bool(value: &str) -> Result<bool, ()> {
     match value.to_ascii_lowercase().as_str() {
         "1" | "true" | "on" | "yes" => Ok(true),
         "0" | "false" | "off" | "no" => Ok(false),
@@ -65,11 +177,25 @@ pub fn sanitize_filename_component(raw: &str) -> String {
 impl ScadParamTemplate {
     /// Read `main_path` and any `include <...>` / `use <...>` recursively.
     pub fn from_scad_tree(main_path: &Path) -> anyhow::Result<Self> {
+        Self::from_scad_tree_with_libs(main_path, &[])
+    }
+
+    /// Like `from_scad_tree`, but an include not found next to the file
+    /// naming it is also looked up in each of `lib_paths`, in order, the way
+    /// OpenSCAD searches `OPENSCADPATH`.
+    pub fn from_scad_tree_with_libs(
+        main_path: &Path,
+        lib_paths: &[PathBuf],
+    ) -> anyhow::Result<Self> {
         let mut visited = HashSet::<PathBuf>::new();
         let mut all_text = String::new();
-        gather_scad_text(main_path, &mut visited, &mut all_text)?;
+        gather_scad_text(main_path, lib_paths, &mut visited, &mut all_text)?;
+        Self::from_scad_text(&all_text)
+    }
 
-        let specs_vec = extract_param_specs(&all_text);
+    /// Build a template from already-gathered .scad source text.
+    pub fn from_scad_text(text: &str) -> anyhow::Result<Self> {
+        let specs_vec = extract_param_specs(text);
         let mut specs = BTreeMap::new();
         let mut defaults = BTreeMap::new();
 
@@ -78,7 +204,100 @@ impl ScadParamTemplate {
             specs.insert(spec.name.clone(), spec);
         }
 
-        Ok(Self { specs, defaults })
+        let mode_formats = extract_mode_formats(text)?;
+        let colorscheme = extract_colorscheme(text)?;
+        let fn_binding = extract_fn_binding(text).filter(|name| specs.contains_key(name));
+
+        Ok(Self {
+            specs,
+            defaults,
+            mode_formats,
+            colorscheme,
+            field_prefix: String::new(),
+            reject_unknown: false,
+            fn_binding,
+        })
+    }
+
+    /// Replace defaults with values from an OpenSCAD Customizer parameter set
+    /// (see `parse_param_set`). Values are validated like form input; names
+    /// the template doesn't declare are skipped and returned for the caller
+    /// to warn about.
+    pub fn apply_param_set(
+        &mut self,
+        values: &BTreeMap<String, String>,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut unknown = Vec::new();
+        let mut params = self.instantiate();
+        // Keys here are SCAD names, not (possibly prefixed) form fields.
+        params.field_prefix.clear();
+        for (name, value) in values {
+            if !self.specs.contains_key(name) {
+                unknown.push(name.clone());
+                continue;
+            }
+            params.set_from_field(name, value)?;
+        }
+        for (name, value) in params.values {
+            if let Some(spec) = self.specs.get_mut(&name) {
+                spec.default = value.clone();
+                spec.required &= !values.contains_key(&name);
+            }
+            self.defaults.insert(name, value);
+        }
+        Ok(unknown)
+    }
+
+    /// Names of the params shown to users, sorted by name.
+    pub fn user_param_names(&self) -> Vec<&str> {
+        self.specs
+            .values()
+            .filter(|s| s.is_user_param)
+            .map(|s| s.name.as_str())
+            .collect()
+    }
+
+    /// Make exactly `names` the user-facing params, overriding the `@param`
+    /// heuristic. Names are SCAD variables, matched case-insensitively; any
+    /// the template doesn't declare is an error.
+    pub fn restrict_user_params(&mut self, names: &[String]) -> anyhow::Result<()> {
+        let wanted: HashSet<String> = names.iter().map(|n| n.to_ascii_uppercase()).collect();
+        let mut unknown: Vec<&String> = wanted
+            .iter()
+            .filter(|n| !self.specs.contains_key(*n))
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            anyhow::bail!(
+                "--expose names not found in the template: {}",
+                unknown
+                    .iter()
+                    .map(|n| n.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        for spec in self.specs.values_mut() {
+            spec.is_user_param = wanted.contains(&spec.name);
+        }
+        Ok(())
+    }
+
+    /// SCAD variable an extra SVG upload field is written to, going by the
+    /// variables the template declares: `svg2` → `SVG_PATH_2`, and
+    /// `svg[back]` → `SVG_PATH_BACK` or `BACK_SVG`. The main `svg` field
+    /// always feeds `SVG_PATH` and isn't mapped here.
+    pub fn svg_field_define(&self, field: &str) -> Option<String> {
+        svg_field_candidates(field)?
+            .into_iter()
+            .find(|name| self.specs.contains_key(name) && is_svg_define(name))
+    }
+
+    /// SCAD variable a `// @param type: file` upload field is written to.
+    pub fn file_field_define(&self, field: &str) -> Option<String> {
+        let scad_name = field_to_scad_name(field, &self.field_prefix)?;
+        let spec = self.specs.get(&scad_name)?;
+        (spec.ty == ParamType::File && spec.is_user_param).then_some(scad_name)
     }
 
     /// Per request, start with discovered defaults.
@@ -86,45 +305,166 @@ impl ScadParamTemplate {
         ScadParams {
             specs: self.specs.clone(),
             values: self.defaults.clone(),
+            field_prefix: self.field_prefix.clone(),
+            reject_unknown: self.reject_unknown,
         }
     }
 }
 
+/// How often a param's submitted values were rejected as the wrong type
+/// before the first warning; later ones come at 10x that, 100x, and so on.
+const TYPE_MISMATCH_WARN_AT: usize = 10;
+
+/// Per-process count of wrong-type values (not a number, not true/false)
+/// per param. Many of them usually mean the template's `@param` type or
+/// default is wrong rather than the clients.
+#[derive(Default)]
+struct TypeMismatches(RwLock<HashMap<String, AtomicUsize>>);
+
+impl TypeMismatches {
+    /// Count one mismatch for `scad_name`, returning the new total when it
+    /// is due a warning.
+    fn record(&self, scad_name: &str) -> Option<usize> {
+        let bump = |counts: &HashMap<String, AtomicUsize>| {
+            counts
+                .get(scad_name)
+                .map(|count| count.fetch_add(1, Ordering::Relaxed) + 1)
+        };
+        let seen = bump(&self.0.read().unwrap());
+        let count = seen.unwrap_or_else(|| {
+            let mut counts = self.0.write().unwrap();
+            counts.entry(scad_name.to_string()).or_default();
+            bump(&counts).unwrap()
+        });
+        let mut warn_at = TYPE_MISMATCH_WARN_AT;
+        while warn_at < count {
+            warn_at *= 10;
+        }
+        (warn_at == count).then_some(count)
+    }
+}
+
+static TYPE_MISMATCHES: LazyLock<TypeMismatches> = LazyLock::new(TypeMismatches::default);
+
+fn note_type_mismatch(spec: &ParamSpec) {
+    if let Some(count) = TYPE_MISMATCHES.record(&spec.name) {
+        warn!(
+            "{count} submitted values for {} were not a valid {:?} (default {}); \
+             is its @param type right?",
+            spec.name, spec.ty, spec.default
+        );
+    }
+}
+
 impl ScadParams {
     /// Update from a multipart field if it matches a discovered param.
     /// Field names in form are expected to be snake_case; SCAD vars are CAPS.
-    pub fn set_from_field(&mut self, field_name: &str, text: &str) -> Result<(), ()> {
+    /// With a `field_prefix`, fields lacking it are ignored. Unknown fields
+    /// are ignored too, unless `reject_unknown` is set.
+    pub fn set_from_field(&mut self, field_name: &str, text: &str) -> Result<(), ParamError> {
+        let err = |kind| ParamError::new(field_name, text, kind);
+        let spec = field_to_scad_name(field_name, &self.field_prefix)
+            .and_then(|scad_name| self.specs.get(&scad_name));
+        let Some(spec) = spec else {
+            return if self.reject_unknown {
+                Err(err(ParamErrorKind::Unknown))
+            } else {
+                Ok(())
+            };
+        };
         if text.trim().is_empty() {
             return Ok(());
         }
-        let scad_name = field_to_scad_name(field_name);
-
-        let Some(spec) = self.specs.get(&scad_name) else {
-            // Unknown field; ignore quietly (matches your old behavior).
-            return Ok(());
+        let scad_name = spec.name.clone();
+        let mismatch = |kind| {
+            note_type_mismatch(spec);
+            err(kind)
         };
 
         let v = match spec.ty {
             ParamType::Bool => {
-                let b = parse_bool(text)?;
+                let b = parse_bool(text).map_err(|_| mismatch(ParamErrorKind::BadBool))?;
                 if b { "true" } else { "false" }.to_string()
             }
             ParamType::Number => {
-                // Validate numeric; keep original string for SCAD.
-                text.parse::<f64>().map_err(|_| ())?;
+                let n = text
+                    .parse::<f64>()
+                    .map_err(|_| mismatch(ParamErrorKind::BadNumber))?;
+                check_step(spec, n).map_err(err)?;
+                fmt_scad_number(text)
+            }
+            ParamType::Integer => {
+                let n = text
+                    .parse::<i64>()
+                    .map_err(|_| mismatch(ParamErrorKind::BadInteger))?;
+                check_step(spec, n as f64).map_err(err)?;
                 text.to_string()
             }
             ParamType::String => {
+                if !spec.options.is_empty() && !spec.options.iter().any(|o| o == text) {
+                    return Err(err(ParamErrorKind::InvalidOption {
+                        options: spec.options.clone(),
+                    }));
+                }
                 // Escape quotes/backslashes minimally, then wrap.
                 let esc = text.replace('\\', "\\\\").replace('"', "\\\"");
                 format!("\"{}\"", esc)
             }
+            // A path typed in could point anywhere on the server.
+            ParamType::File => return Err(err(ParamErrorKind::UploadExpected)),
         };
 
         self.values.insert(scad_name, v);
         Ok(())
     }
 
+    /// The first `required` param still holding the template's expression,
+    /// as a `Missing` error naming its form field.
+    pub fn check_required(&self) -> Result<(), ParamError> {
+        match self
+            .specs
+            .values()
+            .find(|spec| spec.required && self.values.get(&spec.name) == Some(&spec.default))
+        {
+            Some(spec) => Err(ParamError::new(
+                &format!("{}{}", self.field_prefix, spec.name.to_ascii_lowercase()),
+                "",
+                ParamErrorKind::Missing,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Force a string value for `scad_name`, quoting it for SCAD, even if the
+    /// template doesn't declare it.
+    pub fn set_string(&mut self, scad_name: &str, value: &str) {
+        let esc = value.replace('\\', "\\\\").replace('"', "\\\"");
+        self.values
+            .insert(scad_name.to_string(), format!("\"{}\"", esc));
+    }
+
+    /// True for variables holding a server-side path (SVG and file uploads),
+    /// which are left out of logs and render events.
+    pub fn is_path_define(&self, scad_name: &str) -> bool {
+        is_svg_define(scad_name)
+            || self
+                .specs
+                .get(scad_name)
+                .is_some_and(|spec| spec.ty == ParamType::File)
+    }
+
+    /// Pass the uploaded SVG's size in mm (see `svg::parse_svg_dimensions`)
+    /// as `SVG_W` / `SVG_H`, for whichever of them the template declares.
+    pub fn set_svg_size(&mut self, width: f64, height: f64) {
+        for (name, value) in [("SVG_W", width), ("SVG_H", height)] {
+            if self.specs.contains_key(name) {
+                let rounded = (value * 1e6).round() / 1e6;
+                self.values
+                    .insert(name.to_string(), fmt_scad_number(&rounded.to_string()));
+            }
+        }
+    }
+
     /// Iterate "-D NAME=value" fragments in stable order.
     pub fn iter_defines(&self) -> impl Iterator<Item = String> + '_ {
         self.values.iter().map(|(k, v)| format!("{k}={v}"))
@@ -136,15 +476,132 @@ impl ScadParams {
     }
 }
 
+/// With `enforce_step`, `value` must be a whole multiple of the spec's
+/// step, give or take float rounding (0.3 is a multiple of 0.1).
+fn check_step(spec: &ParamSpec, value: f64) -> Result<(), ParamErrorKind> {
+    match spec.step {
+        Some(step) if spec.enforce_step => {
+            let steps = value / step;
+            if (steps - steps.round()).abs() <= STEP_TOLERANCE {
+                Ok(())
+            } else {
+                Err(ParamErrorKind::OffStep { step })
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Slack, in steps, for values that are on the grid but don't divide exactly.
+const STEP_TOLERANCE: f64 = 1e-6;
+
+/// Stringify a JSON value the way a form would submit it.
+pub fn json_value_to_field(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Read one set out of an OpenSCAD Customizer parameter file:
+/// `{"parameterSets": {"<name>": {"VAR": "value", ...}}}`.
+/// `set_name` may be omitted when the file holds exactly one set.
+pub fn parse_param_set(
+    text: &str,
+    set_name: Option<&str>,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let doc: serde_json::Value = serde_json::from_str(text).context("parse parameter set JSON")?;
+    let Some(sets) = doc.get("parameterSets").and_then(|v| v.as_object()) else {
+        anyhow::bail!("parameter set file has no \"parameterSets\" object");
+    };
+    let set = match set_name {
+        Some(name) => sets
+            .get(name)
+            .with_context(|| format!("parameter set '{name}' not found"))?,
+        None if sets.len() == 1 => sets.values().next().unwrap(),
+        None => anyhow::bail!(
+            "parameter set file holds {} sets; choose one with --param-set-name ({})",
+            sets.len(),
+            sets.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let Some(set) = set.as_object() else {
+        anyhow::bail!("parameter set is not an object");
+    };
+    Ok(set
+        .iter()
+        .map(|(k, v)| (k.clone(), json_value_to_field(v)))
+        .collect())
+}
+
+/// Names from an `--expose-file`: comma or newline separated, `#` comments.
+pub fn parse_name_list(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// True for field names shaped like an extra SVG upload (`svg2`, `svg[back]`).
+pub fn is_extra_svg_field(field: &str) -> bool {
+    svg_field_candidates(field).is_some()
+}
+
+/// True for SCAD variables that take an SVG path: `SVG_PATH*` or `*_SVG`.
+pub fn is_svg_define(scad_name: &str) -> bool {
+    scad_name.starts_with("SVG_PATH") || scad_name.ends_with("_SVG")
+}
+
 // -------- internals --------
 
-fn field_to_scad_name(field: &str) -> String {
-    field.to_ascii_uppercase()
+fn svg_field_candidates(field: &str) -> Option<Vec<String>> {
+    let rest = field.strip_prefix("svg")?;
+    if let Some(key) = rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        let valid = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return None;
+        }
+        let key = key.to_ascii_uppercase();
+        return Some(vec![format!("SVG_PATH_{key}"), format!("{key}_SVG")]);
+    }
+    match rest.parse::<u32>() {
+        Ok(n) if n >= 2 && !rest.starts_with('0') => Some(vec![format!("SVG_PATH_{n}")]),
+        _ => None,
+    }
+}
+
+/// SCAD variable a form field names: `prefix` stripped, then upper-cased.
+/// `None` when the field lacks the prefix.
+pub fn field_to_scad_name(field: &str, prefix: &str) -> Option<String> {
+    field
+        .strip_prefix(prefix)
+        .map(|name| name.to_ascii_uppercase())
+}
+
+/// Spell a value for a float param the way its default is spelled: whole
+/// numbers get a `.0` (`5` → `5.0`), anything else is kept as typed.
+///
+/// OpenSCAD has a single number type, so this doesn't change geometry. It
+/// keeps `5` and `5.0` from producing different `-D` arguments (and so
+/// different `dump_scad` output) for the same render, and a float param
+/// never reads like an integer one in the generated command.
+pub fn fmt_scad_number(text: &str) -> String {
+    let text = text.trim();
+    if text.parse::<i64>().is_ok() {
+        format!("{text}.0")
+    } else {
+        text.to_string()
+    }
 }
 
 /// Recursively gather text from main file and its includes.
 fn gather_scad_text(
     path: &Path,
+    lib_paths: &[PathBuf],
     visited: &mut HashSet<PathBuf>,
     out: &mut String,
 ) -> anyhow::Result<()> {
@@ -165,9 +622,12 @@ fn gather_scad_text(
     let include_re = Regex::new(r#"(?m)^\s*(?:include|use)\s*<([^>]+)>\s*;"#).unwrap();
     for cap in include_re.captures_iter(&text) {
         let rel = cap[1].trim();
-        let inc = dir.join(rel);
-        if inc.exists() {
-            gather_scad_text(&inc, visited, out)?;
+        let found = std::iter::once(dir)
+            .chain(lib_paths.iter().map(PathBuf::as_path))
+            .map(|root| root.join(rel))
+            .find(|inc| inc.exists());
+        if let Some(inc) = found {
+            gather_scad_text(&inc, lib_paths, visited, out)?;
         }
     }
 
@@ -182,6 +642,8 @@ fn gather_scad_text(
 /// - `$fn` etc are ignored.
 /// - If any line uses `// @param`, we *only* accept marked lines as user params.
 ///   (this prevents derived globals like FIT, LOGO_TARGET from becoming overrideable)
+/// - Numeric defaults that aren't literals (`FIT = CLEARANCE/2;`) are derived
+///   values and never user params, marked or not.
 pub fn extract_param_specs(text: &str) -> Vec<ParamSpec> {
     // Capture: NAME = RHS;  // optional comment
     let assign_re =
@@ -209,33 +671,96 @@ pub fn extract_param_specs(text: &str) -> Vec<ParamSpec> {
         raws.push((name, rhs, is_marked, comment));
     }
 
+    // Numeric values so far, in file order, for evaluating derived defaults.
+    let mut known = BTreeMap::<String, f64>::new();
     raws.into_iter()
-        .filter_map(|(name, rhs, is_marked, comment)| {
-            let is_user_param = if any_marked { is_marked } else { true };
-
+        .map(|(name, rhs, is_marked, comment)| {
             let ty = if rhs.trim_start().starts_with('"') {
-                ParamType::String
+                if is_marked && is_file_param(&comment) {
+                    ParamType::File
+                } else {
+                    ParamType::String
+                }
             } else {
                 match rhs.to_ascii_lowercase().trim() {
                     "true" | "false" => ParamType::Bool,
+                    // Whole literals only; `12.0` or `SEG*2` stay Number.
+                    lit if lit.parse::<i64>().is_ok() => ParamType::Integer,
                     _ => ParamType::Number,
                 }
             };
 
+            let derived = ty == ParamType::Number && rhs.trim().parse::<f64>().is_err();
+            let computed = if derived {
+                crate::expr::eval(&rhs, |var| known.get(var).copied())
+            } else {
+                None
+            };
+            let required = is_marked && derived && computed.is_none();
+            // Required params stay in the form so a value can be entered.
+            let is_user_param = (!derived || required) && (is_marked || !any_marked);
+            let value = computed.or_else(|| rhs.trim().parse().ok());
+            if let Some(value) = value.filter(|v: &f64| v.is_finite()) {
+                known.insert(name.clone(), value);
+            }
+
             let options = parse_options_from_comment(&comment);
+            let advanced = is_marked_advanced(&comment);
+            let form_default = parse_form_default(&comment);
+            let step = parse_step(&comment);
+            let enforce_step = step.is_some() && has_param_flag(&comment, "enforce-step");
 
-            Some(ParamSpec {
+            ParamSpec {
                 name,
                 default: rhs,
                 ty,
                 is_user_param,
                 comment,
                 options,
-            })
+                advanced,
+                form_default,
+                step,
+                enforce_step,
+                computed,
+                required,
+            }
         })
         .collect()
 }
 
+/// Extract `// @mode-formats MODE: fmt|fmt` lines declaring which export
+/// formats a MODE value supports (e.g. `// @mode-formats cut: dxf|svg`).
+fn extract_mode_formats(text: &str) -> anyhow::Result<BTreeMap<String, Vec<ExportFormat>>> {
+    let re = Regex::new(r#"(?m)//\s*@mode-formats\s+([^:\s]+)\s*:\s*(.*)$"#).unwrap();
+    let mut out = BTreeMap::new();
+    for cap in re.captures_iter(text) {
+        let formats = cap[2]
+            .split(['|', ','])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<ExportFormat>().map_err(anyhow::Error::msg))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("@mode-formats {}", &cap[1]))?;
+        out.insert(cap[1].to_string(), formats);
+    }
+    Ok(out)
+}
+
+/// The variable in a top-level `$fn = SEG;`, if `$fn` is bound to one.
+fn extract_fn_binding(text: &str) -> Option<String> {
+    let re = Regex::new(r#"(?m)^\s*\$fn\s*=\s*([A-Z][A-Z0-9_]*)\s*;"#).unwrap();
+    re.captures(text).map(|cap| cap[1].to_string())
+}
+
+/// Extract a `// @colorscheme NAME` line (e.g. `// @colorscheme Tomorrow Night`).
+fn extract_colorscheme(text: &str) -> anyhow::Result<Option<&'static str>> {
+    let re = Regex::new(r#"(?m)//\s*@colorscheme\s+(.*)$"#).unwrap();
+    re.captures(text)
+        .map(|cap| parse_colorscheme(&cap[1]).map_err(anyhow::Error::msg))
+        .transpose()
+        .context("@colorscheme")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -265,10 +790,79 @@ $fn = 200;
         let d = specs.iter().find(|s| s.name == "COASTER_D").unwrap();
         assert_eq!(d.ty, ParamType::Number);
 
+        let fit = specs.iter().find(|s| s.name == "FIT").unwrap();
+        assert_eq!(fit.ty, ParamType::Number);
+        assert!(!fit.is_user_param, "expression defaults aren't editable");
+        assert!(d.is_user_param);
+
         let b = specs.iter().find(|s| s.name == "USE_SPINNER").unwrap();
         assert_eq!(b.ty, ParamType::Bool);
     }
 
+    #[test]
+    fn includes_fall_back_to_lib_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let template_dir = dir.path().join("template");
+        let lib_dir = dir.path().join("libs");
+        fs::create_dir_all(template_dir.join("parts")).unwrap();
+        fs::create_dir_all(lib_dir.join("shapes")).unwrap();
+        fs::create_dir_all(lib_dir.join("parts")).unwrap();
+        let main = template_dir.join("main.scad");
+        fs::write(
+            &main,
+            "include <parts/base.scad>;\nuse <shapes/star.scad>;\n",
+        )
+        .unwrap();
+        fs::write(
+            template_dir.join("parts/base.scad"),
+            "BASE_H = 3; // @param\n",
+        )
+        .unwrap();
+        fs::write(lib_dir.join("shapes/star.scad"), "POINTS = 5; // @param\n").unwrap();
+        // A same-named file next to the template wins over the lib path.
+        fs::write(lib_dir.join("parts/base.scad"), "SHADOWED = 1; // @param\n").unwrap();
+
+        let tmpl = ScadParamTemplate::from_scad_tree(&main).unwrap();
+        assert!(tmpl.specs.contains_key("BASE_H"));
+        assert!(!tmpl.specs.contains_key("POINTS"));
+
+        let tmpl = ScadParamTemplate::from_scad_tree_with_libs(&main, &[lib_dir]).unwrap();
+        assert!(tmpl.specs.contains_key("BASE_H"));
+        assert!(tmpl.specs.contains_key("POINTS"));
+        assert!(!tmpl.specs.contains_key("SHADOWED"));
+    }
+
+    #[test]
+    fn file_typed_params_take_uploads_only() {
+        let scad = r#"
+LOGO = "placeholder.svg"; // @param type: file
+LABEL = "x"; // @param
+TEXTURE = "grain.png"; // type: file, but not marked
+"#;
+        let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+        assert_eq!(tmpl.specs["LOGO"].ty, ParamType::File);
+        assert_eq!(tmpl.specs["LABEL"].ty, ParamType::String);
+        assert_eq!(tmpl.specs["TEXTURE"].ty, ParamType::String);
+        assert_eq!(tmpl.file_field_define("logo").as_deref(), Some("LOGO"));
+        assert_eq!(tmpl.file_field_define("label"), None);
+
+        let mut p = tmpl.instantiate();
+        let err = p.set_from_field("logo", "/etc/passwd").unwrap_err();
+        assert_eq!(err.kind, ParamErrorKind::UploadExpected);
+        assert!(p.is_path_define("LOGO"));
+        assert!(!p.is_path_define("LABEL"));
+    }
+
+    #[test]
+    fn fn_binding_names_a_declared_param() {
+        let tmpl = ScadParamTemplate::from_scad_text("SEG = 64; // @param\n$fn = SEG;\n").unwrap();
+        assert_eq!(tmpl.fn_binding.as_deref(), Some("SEG"));
+        let tmpl = ScadParamTemplate::from_scad_text("SEG = 64;\n$fn = fn;\n").unwrap();
+        assert_eq!(tmpl.fn_binding, None);
+        let tmpl = ScadParamTemplate::from_scad_text("$fn = OTHER;\n").unwrap();
+        assert_eq!(tmpl.fn_binding, None);
+    }
+
     #[test]
     fn marker_filtering_works() {
         let scad = r#"
@@ -286,6 +880,22 @@ FIT = CLEARANCE/2;
         assert_eq!(user, vec!["MODE", "COASTER_D"]);
     }
 
+    #[test]
+    fn type_mismatches_warn_at_the_threshold_then_each_tenfold() {
+        let mismatches = TypeMismatches::default();
+        let warned: Vec<usize> = (0..1000)
+            .filter_map(|_| mismatches.record("WIDTH"))
+            .collect();
+        assert_eq!(warned, [10, 100, 1000]);
+
+        // Counted per param.
+        for _ in 1..TYPE_MISMATCH_WARN_AT {
+            assert_eq!(mismatches.record("HEIGHT"), None);
+        }
+        assert_eq!(mismatches.record("HEIGHT"), Some(10));
+        assert_eq!(mismatches.record("WIDTH"), None);
+    }
+
     #[test]
     fn set_from_field_only_updates_known_params() {
         let scad = r#"
@@ -293,19 +903,7 @@ MODE = "base";
 COASTER_D = 101.6;
 USE_SPINNER = true;
 "#;
-        let _tmpl = ScadParamTemplate::from_scad_tree(
-            Path::new("/dev/null"), // not used here
-        );
-
-        // cheat: build directly from text
-        let specs_vec = extract_param_specs(scad);
-        let mut specs = BTreeMap::new();
-        let mut defaults = BTreeMap::new();
-        for s in specs_vec {
-            defaults.insert(s.name.clone(), s.default.clone());
-            specs.insert(s.name.clone(), s);
-        }
-        let tmpl = ScadParamTemplate { specs, defaults };
+        let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
 
         let mut p = tmpl.instantiate();
         p.set_from_field("mode", "preview").unwrap();
@@ -316,24 +914,308 @@ USE_SPINNER = true;
         assert_eq!(p.get_raw("USE_SPINNER").unwrap(), "false");
         assert!(p.get_raw("UNKNOWN").is_none());
     }
+
+    #[test]
+    fn expose_allowlist_overrides_markers() {
+        let mut tmpl = ScadParamTemplate::from_scad_text(
+            r#"
+MODE = "base"; // @param
+COASTER_D = 101.6; // @param
+BOTTOM_SKIN = 0.6;
+"#,
+        )
+        .unwrap();
+        assert_eq!(tmpl.user_param_names(), vec!["COASTER_D", "MODE"]);
+
+        let names = parse_name_list("bottom_skin, MODE # the basics\n\n# nothing here\n");
+        assert_eq!(names, vec!["bottom_skin", "MODE"]);
+        tmpl.restrict_user_params(&names).unwrap();
+        assert_eq!(tmpl.user_param_names(), vec!["BOTTOM_SKIN", "MODE"]);
+
+        let err = tmpl
+            .restrict_user_params(&["MODE".to_string(), "NOPE".to_string()])
+            .unwrap_err();
+        assert!(err.to_string().contains("NOPE"));
+    }
+
+    #[test]
+    fn unknown_fields_ignored_unless_rejected() {
+        let mut tmpl = ScadParamTemplate::from_scad_text("COASTER_D = 101.6;\n").unwrap();
+        let mut p = tmpl.instantiate();
+        p.set_from_field("coster_d", "90").unwrap();
+        assert_eq!(p.get_raw("COASTER_D").unwrap(), "101.6");
+
+        tmpl.reject_unknown = true;
+        let mut p = tmpl.instantiate();
+        let err = p.set_from_field("coster_d", "90").unwrap_err();
+        assert_eq!(err.kind, ParamErrorKind::Unknown);
+        assert_eq!(err.to_string(), "coster_d: no such parameter");
+        p.set_from_field("coaster_d", "90").unwrap();
+        assert_eq!(p.get_raw("COASTER_D").unwrap(), "90.0");
+    }
+
+    #[test]
+    fn whole_numbers_into_float_params_gain_a_decimal() {
+        let tmpl = ScadParamTemplate::from_scad_text("BASE_H = 2.5;\n").unwrap();
+        for (input, expected) in [("5", "5.0"), ("5.0", "5.0"), ("5.5", "5.5"), ("-3", "-3.0")] {
+            let mut p = tmpl.instantiate();
+            p.set_from_field("base_h", input).unwrap();
+            assert_eq!(p.get_raw("BASE_H").unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn integer_params_reject_fractions() {
+        let tmpl =
+            ScadParamTemplate::from_scad_text("SEG = 12;\nLIP = -2;\nWALL = 2.0;\n").unwrap();
+        assert_eq!(tmpl.specs["SEG"].ty, ParamType::Integer);
+        assert_eq!(tmpl.specs["LIP"].ty, ParamType::Integer);
+        assert_eq!(tmpl.specs["WALL"].ty, ParamType::Number);
+
+        let mut p = tmpl.instantiate();
+        let err = p.set_from_field("seg", "12.3").unwrap_err();
+        assert_eq!(err.kind, ParamErrorKind::BadInteger);
+        assert!(err.to_string().contains("whole number"));
+
+        p.set_from_field("seg", "12").unwrap();
+        assert_eq!(p.get_raw("SEG").unwrap(), "12");
+        p.set_from_field("wall", "2.5").unwrap();
+        assert_eq!(p.get_raw("WALL").unwrap(), "2.5");
+    }
+
+    #[test]
+    fn svg_fields_map_to_declared_svg_defines() {
+        let tmpl = ScadParamTemplate::from_scad_text(
+            r#"
+SVG_PATH = "front.svg";
+SVG_PATH_2 = "second.svg";
+BACK_SVG = "back.svg";
+SVG_PATH_LID = "lid.svg";
+COASTER_D = 101.6;
+"#,
+        )
+        .unwrap();
+
+        let define = |field| tmpl.svg_field_define(field);
+        assert_eq!(define("svg2").as_deref(), Some("SVG_PATH_2"));
+        assert_eq!(define("svg[back]").as_deref(), Some("BACK_SVG"));
+        assert_eq!(define("svg[lid]").as_deref(), Some("SVG_PATH_LID"));
+        assert_eq!(define("svg3"), None);
+        assert_eq!(define("svg[coaster_d]"), None);
+        assert_eq!(define("svg"), None);
+
+        assert!(is_extra_svg_field("svg3"));
+        assert!(is_extra_svg_field("svg[top]"));
+        assert!(!is_extra_svg_field("svg"));
+        assert!(!is_extra_svg_field("svg1"));
+        assert!(!is_extra_svg_field("svg02"));
+        assert!(!is_extra_svg_field("svg_scale"));
+        assert!(!is_extra_svg_field("svg[a b]"));
+    }
+
+    #[test]
+    fn field_prefix_is_stripped_and_required() {
+        assert_eq!(
+            field_to_scad_name("scad_coaster_d", "scad_").as_deref(),
+            Some("COASTER_D")
+        );
+        assert_eq!(field_to_scad_name("coaster_d", "scad_"), None);
+
+        let mut tmpl = ScadParamTemplate::from_scad_text("COASTER_D = 101.6;\n").unwrap();
+        tmpl.field_prefix = "scad_".to_string();
+        let mut p = tmpl.instantiate();
+        p.set_from_field("coaster_d", "50").unwrap();
+        assert_eq!(p.get_raw("COASTER_D").unwrap(), "101.6");
+        p.set_from_field("scad_coaster_d", "90").unwrap();
+        assert_eq!(p.get_raw("COASTER_D").unwrap(), "90.0");
+    }
+}
+
+/// `// @param advanced ...`: the first word after the marker is `advanced`.
+fn is_marked_advanced(comment: &str) -> bool {
+    comment
+        .split_once("@param")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .is_some_and(|word| word.eq_ignore_ascii_case("advanced"))
+}
+
+/// `// @param ... enforce-step ...`: `flag` is one of the words after the marker.
+fn has_param_flag(comment: &str, flag: &str) -> bool {
+    comment.split_once("@param").is_some_and(|(_, rest)| {
+        rest.split_whitespace()
+            .any(|word| word.eq_ignore_ascii_case(flag))
+    })
+}
+
+/// `// @param type: file` (or `type file`) on a string param.
+fn is_file_param(comment: &str) -> bool {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    words.windows(2).any(|pair| {
+        let keyword = pair[0].strip_suffix(':').unwrap_or(pair[0]);
+        keyword.eq_ignore_ascii_case("type") && pair[1].eq_ignore_ascii_case("file")
+    })
+}
+
+/// `step 0.5` or `step: 0.5` in a param comment; only positive steps count.
+fn parse_step(comment: &str) -> Option<f64> {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    words.windows(2).find_map(|pair| {
+        let keyword = pair[0].strip_suffix(':').unwrap_or(pair[0]);
+        if !keyword.eq_ignore_ascii_case("step") {
+            return None;
+        }
+        pair[1]
+            .parse::<f64>()
+            .ok()
+            .filter(|s| s.is_finite() && *s > 0.0)
+    })
 }
 
 fn parse_options_from_comment(comment: &str) -> Vec<String> {
     // Accept e.g.:
     //   // @param options: base|inlay|magnet|preview
     //   // options: octagon, circle
+    //   // options: "base", "inlay".
     let lower = comment.to_ascii_lowercase();
     let Some(idx) = lower.find("options:") else {
         return Vec::new();
     };
 
-    let rest = &comment[idx + "options:".len()..];
-    rest.split(|c: char| c == '|' || c == ',')
-        .map(|s| s.trim().to_string())
+    // A list written as a sentence may end in a period.
+    let mut rest = comment[idx + "options:".len()..].trim_end();
+    if let Some(end) = rest.to_ascii_lowercase().find("default:") {
+        rest = rest[..end].trim_end();
+    }
+    let rest = rest.strip_suffix('.').unwrap_or(rest);
+    rest.split(['|', ','])
+        .map(|s| strip_matching_quotes(s.trim()).trim().to_string())
         .filter(|s| !s.is_empty())
         .collect()
 }
 
+/// The value after `default:` in a param comment: a double-quoted string
+/// (quotes kept, so it reads like an OpenSCAD literal) or a single word.
+fn parse_form_default(comment: &str) -> Option<String> {
+    let idx = comment.to_ascii_lowercase().find("default:")?;
+    let rest = comment[idx + "default:".len()..].trim_start();
+    let value = match rest.strip_prefix('"') {
+        Some(quoted) => &rest[..quoted.find('"')? + 2],
+        None => rest.split_whitespace().next()?,
+    };
+    Some(value.to_string())
+}
+
+/// `"base"` or `'base'` → `base`; anything else unchanged.
+fn strip_matching_quotes(s: &str) -> &str {
+    ['"', '\'']
+        .into_iter()
+        .find_map(|q| s.strip_prefix(q)?.strip_suffix(q))
+        .unwrap_or(s)
+}
+
+#[test]
+fn set_from_field_reports_field_and_reason() {
+    let tmpl = ScadParamTemplate::from_scad_text(
+        r#"
+COASTER_D = 101.6; // @param
+USE_SPINNER = true; // @param
+SHAPE = "octagon"; // @param options: octagon|circle
+"#,
+    )
+    .unwrap();
+    let mut p = tmpl.instantiate();
+
+    let err = p.set_from_field("coaster_d", "big").unwrap_err();
+    assert_eq!(err.field, "coaster_d");
+    assert_eq!(err.kind, ParamErrorKind::BadNumber);
+
+    let err = p.set_from_field("use_spinner", "maybe").unwrap_err();
+    assert_eq!(err.kind, ParamErrorKind::BadBool);
+
+    let err = p.set_from_field("shape", "square").unwrap_err();
+    assert!(matches!(err.kind, ParamErrorKind::InvalidOption { .. }));
+    assert!(err.to_string().contains("octagon, circle"));
+
+    p.set_from_field("shape", "circle").unwrap();
+    assert_eq!(p.get_raw("SHAPE").unwrap(), "\"circle\"");
+}
+
+#[test]
+fn param_set_overrides_defaults() {
+    let json = r#"{
+  "fileFormatVersion": "1",
+  "parameterSets": {
+    "small": { "COASTER_D": "90", "MODE": "inlay", "USE_SPINNER": "false", "BOGUS": "1" }
+  }
+}"#;
+    let values = parse_param_set(json, None).unwrap();
+    assert_eq!(values["COASTER_D"], "90");
+    assert_eq!(parse_param_set(json, Some("small")).unwrap(), values);
+    assert!(parse_param_set(json, Some("large")).is_err());
+
+    let mut tmpl = ScadParamTemplate::from_scad_text(
+        r#"
+MODE = "base"; // @param
+COASTER_D = 101.6; // @param
+USE_SPINNER = true; // @param
+"#,
+    )
+    .unwrap();
+    let unknown = tmpl.apply_param_set(&values).unwrap();
+    assert_eq!(unknown, vec!["BOGUS"]);
+    assert_eq!(tmpl.defaults["COASTER_D"], "90.0");
+    assert_eq!(tmpl.defaults["MODE"], "\"inlay\"");
+    assert_eq!(tmpl.specs["USE_SPINNER"].default, "false");
+
+    let bad = BTreeMap::from([("COASTER_D".to_string(), "huge".to_string())]);
+    assert!(tmpl.apply_param_set(&bad).is_err());
+}
+
+#[test]
+fn param_set_requires_name_when_ambiguous() {
+    let json = r#"{"parameterSets": {"a": {}, "b": {}}}"#;
+    let err = parse_param_set(json, None).unwrap_err();
+    assert!(err.to_string().contains("--param-set-name"));
+}
+
+#[test]
+fn advanced_marker_parses() {
+    let scad = r#"
+BOSS_H = 0.8; // @param advanced boss height
+COASTER_D = 101.6; // @param diameter, tweak for advanced users
+MODE = "base"; // @param advanced options: base|inlay
+"#;
+    let specs = extract_param_specs(scad);
+    let get = |n: &str| specs.iter().find(|s| s.name == n).unwrap();
+    assert!(get("BOSS_H").advanced);
+    assert!(!get("COASTER_D").advanced);
+    assert!(get("MODE").advanced);
+    assert_eq!(get("MODE").options, vec!["base", "inlay"]);
+}
+
+#[test]
+fn mode_formats_parse_from_comment() {
+    let scad = r#"
+MODE="base"; // @param options: base|cut
+// @mode-formats cut: dxf|svg
+"#;
+    let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+    assert_eq!(
+        tmpl.mode_formats.get("cut"),
+        Some(&vec![ExportFormat::Dxf, ExportFormat::Svg])
+    );
+    assert!(ScadParamTemplate::from_scad_text("// @mode-formats cut: gif\n").is_err());
+}
+
+#[test]
+fn colorscheme_parses_from_comment() {
+    let tmpl = ScadParamTemplate::from_scad_text("// @colorscheme deepocean\n").unwrap();
+    assert_eq!(tmpl.colorscheme, Some("DeepOcean"));
+    let plain = ScadParamTemplate::from_scad_text("A=1;\n").unwrap();
+    assert_eq!(plain.colorscheme, None);
+    assert!(ScadParamTemplate::from_scad_text("// @colorscheme Neon\n").is_err());
+}
+
 #[test]
 fn options_parse_from_comment() {
     let scad = r#"
@@ -343,3 +1225,122 @@ MODE="base"; // @param options: base|inlay|magnet|preview
     let mode = specs.iter().find(|s| s.name == "MODE").unwrap();
     assert_eq!(mode.options, vec!["base", "inlay", "magnet", "preview"]);
 }
+
+#[test]
+fn form_default_parses_from_comment() {
+    let scad = r#"
+COASTER_D = 101.6; // @param default: 90
+LABEL = "hi"; // @param default: "hello world" options: hi|hello world
+SHAPE = "octagon"; // @param options: octagon|circle default: "circle"
+SEG = 12; // @param advanced
+"#;
+    let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+    let spec = |name: &str| tmpl.specs[name].clone();
+
+    assert_eq!(spec("COASTER_D").form_default.as_deref(), Some("90"));
+    assert_eq!(spec("COASTER_D").form_value(), "90");
+    assert_eq!(spec("LABEL").form_value(), "\"hello world\"");
+    assert_eq!(spec("LABEL").options, ["hi", "hello world"]);
+    assert_eq!(spec("SHAPE").form_value(), "\"circle\"");
+    assert_eq!(spec("SHAPE").options, ["octagon", "circle"]);
+    assert_eq!(spec("SEG").form_default, None);
+    assert_eq!(spec("SEG").form_value(), "12");
+
+    // The render baseline stays the SCAD value.
+    assert_eq!(tmpl.defaults["COASTER_D"], "101.6");
+    assert_eq!(tmpl.instantiate().get_raw("COASTER_D").unwrap(), "101.6");
+}
+
+#[test]
+fn derived_defaults_are_computed_for_display() {
+    let scad = r#"
+COASTER_D = 101.6; // @param
+HALF = 101.6/2;
+BASE_R = COASTER_D/2 - 0.8;
+LIP = sqrt(BASE_R);
+"#;
+    let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+    assert_eq!(tmpl.specs["HALF"].computed, Some(50.8));
+    assert_eq!(tmpl.specs["BASE_R"].computed, Some(50.0));
+    assert_eq!(tmpl.specs["LIP"].computed, None);
+    assert_eq!(tmpl.specs["COASTER_D"].computed, None);
+    // Still passed through as written.
+    assert!(!tmpl.specs["BASE_R"].is_user_param);
+    assert_eq!(tmpl.defaults["BASE_R"], "COASTER_D/2 - 0.8");
+}
+
+#[test]
+fn marked_params_without_usable_default_are_required() {
+    let scad = r#"
+BASE = 4; // @param
+WIDTH = sqrt(BASE); // @param
+HALF = BASE/2; // @param
+INNER = sqrt(BASE);
+"#;
+    let mut tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+    assert!(tmpl.specs["WIDTH"].required);
+    assert!(tmpl.specs["WIDTH"].is_user_param);
+    assert!(!tmpl.specs["HALF"].required);
+    assert!(!tmpl.specs["INNER"].required);
+
+    let mut p = tmpl.instantiate();
+    let err = p.check_required().unwrap_err();
+    assert_eq!(err.kind, ParamErrorKind::Missing);
+    assert_eq!(err.field, "width");
+    p.set_from_field("width", "3").unwrap();
+    assert!(p.check_required().is_ok());
+
+    // A parameter set gives it a real baseline.
+    let values = BTreeMap::from([("WIDTH".to_string(), "2".to_string())]);
+    tmpl.apply_param_set(&values).unwrap();
+    assert!(!tmpl.specs["WIDTH"].required);
+    assert!(tmpl.instantiate().check_required().is_ok());
+}
+
+#[test]
+fn enforce_step_rejects_off_grid_numbers() {
+    let scad = r#"
+WALL = 1.2; // @param enforce-step step 0.4
+HEIGHT = 10; // @param step: 2 enforce-step
+GAP = 0.5; // @param step 0.5
+"#;
+    let tmpl = ScadParamTemplate::from_scad_text(scad).unwrap();
+    assert_eq!(tmpl.specs["WALL"].step, Some(0.4));
+    assert!(tmpl.specs["WALL"].enforce_step);
+    assert!(!tmpl.specs["GAP"].enforce_step);
+
+    let mut p = tmpl.instantiate();
+    p.set_from_field("wall", "2.0").unwrap();
+    p.set_from_field("wall", "1.6").unwrap();
+    assert_eq!(p.get_raw("WALL").unwrap(), "1.6");
+    p.set_from_field("height", "14").unwrap();
+
+    let err = p.set_from_field("wall", "1.5").unwrap_err();
+    assert_eq!(err.kind, ParamErrorKind::OffStep { step: 0.4 });
+    assert_eq!(err.to_string(), "wall: 1.5 is not a multiple of 0.4");
+    assert_eq!(p.get_raw("WALL").unwrap(), "1.6");
+    let err = p.set_from_field("height", "13").unwrap_err();
+    assert_eq!(err.kind, ParamErrorKind::OffStep { step: 2.0 });
+
+    // Without the flag the step is only a hint.
+    p.set_from_field("gap", "0.7").unwrap();
+}
+
+#[test]
+fn options_parse_quoted_and_sentence_lists() {
+    let scad = r#"
+MODE="base"; // @param options: "base", "inlay"
+SHAPE="octagon"; // @param options: octagon, circle.
+LID="flat"; // @param options: 'flat' | "domed".
+SIZE="1.5"; // @param options: 1.5|2.5
+"#;
+    let specs = extract_param_specs(scad);
+    let options = |name: &str| {
+        let spec = specs.iter().find(|s| s.name == name).unwrap();
+        spec.options.clone()
+    };
+    assert_eq!(options("MODE"), ["base", "inlay"]);
+    assert_eq!(options("SHAPE"), ["octagon", "circle"]);
+    assert_eq!(options("LID"), ["flat", "domed"]);
+    assert_eq!(options("SIZE"), ["1.5", "2.5"]);
+}
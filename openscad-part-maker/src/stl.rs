@@ -0,0 +1,712 @@
+//! Helpers for post-processing STL files produced by OpenSCAD.
+//!
+//! Both binary and ASCII STL are handled; OpenSCAD writes ASCII by default.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Size of the binary STL header (80 byte comment + u32 triangle count).
+pub const BINARY_HEADER_LEN: usize = 84;
+
+/// Size of one binary STL triangle record (normal, 3 vertices, attribute).
+pub const BINARY_RECORD_LEN: usize = 50;
+
+/// True if `bytes` is laid out as a binary STL (header count matches length).
+pub fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < BINARY_HEADER_LEN {
+        return false;
+    }
+    binary_len_matches(&bytes[..BINARY_HEADER_LEN], bytes.len())
+}
+
+/// True if the triangle count in `header` accounts for exactly `len` bytes.
+fn binary_len_matches(header: &[u8], len: usize) -> bool {
+    let count = u32::from_le_bytes([header[80], header[81], header[82], header[83]]) as usize;
+    count
+        .checked_mul(BINARY_RECORD_LEN)
+        .and_then(|n| n.checked_add(BINARY_HEADER_LEN))
+        == Some(len)
+}
+
+/// Number of triangles in an STL: the header count for binary files, the
+/// number of `facet` blocks for ASCII ones.
+pub fn triangle_count(bytes: &[u8]) -> u64 {
+    if is_binary(bytes) {
+        u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as u64
+    } else {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .filter(|line| line.trim_start().starts_with("facet"))
+            .count() as u64
+    }
+}
+
+/// `triangle_count` for a file, reading it line by line instead of loading
+/// it whole.
+pub fn triangle_count_file(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len() as usize;
+    let mut header = [0u8; BINARY_HEADER_LEN];
+    if len >= BINARY_HEADER_LEN {
+        file.read_exact(&mut header)?;
+        if binary_len_matches(&header, len) {
+            return Ok(u32::from_le_bytes([header[80], header[81], header[82], header[83]]) as u64);
+        }
+        file.seek(SeekFrom::Start(0))?;
+    }
+    let mut count = 0;
+    for line in BufReader::new(file).split(b'\n') {
+        if line?.trim_ascii_start().starts_with(b"facet") {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Every vertex in an STL, three per triangle, in file order.
+pub fn vertices(bytes: &[u8]) -> Vec<[f32; 3]> {
+    if is_binary(bytes) {
+        bytes[BINARY_HEADER_LEN..]
+            .chunks_exact(BINARY_RECORD_LEN)
+            .flat_map(|record| record[12..48].chunks_exact(12))
+            .map(|v| [0, 4, 8].map(|i| f32::from_le_bytes(v[i..i + 4].try_into().unwrap())))
+            .collect()
+    } else {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .filter_map(|line| line.trim_start().strip_prefix("vertex"))
+            .filter_map(|rest| {
+                let mut coords = rest.split_whitespace().map(|c| c.parse::<f32>().ok());
+                Some([coords.next()??, coords.next()??, coords.next()??])
+            })
+            .collect()
+    }
+}
+
+/// Axis-aligned bounding box as `(min, max)`; `None` for an empty mesh.
+pub fn bounds(bytes: &[u8]) -> Option<([f32; 3], [f32; 3])> {
+    let mut points = vertices(bytes).into_iter();
+    let first = points.next()?;
+    Some(points.fold((first, first), |(min, max), p| {
+        (
+            [0, 1, 2].map(|i| min[i].min(p[i])),
+            [0, 1, 2].map(|i| max[i].max(p[i])),
+        )
+    }))
+}
+
+/// Enclosed volume in cubic file units (mm³ for OpenSCAD output): the sum
+/// of the signed tetrahedra each triangle makes with the origin (divergence
+/// theorem). Meaningful for closed meshes; either winding gives the same
+/// result.
+pub fn volume(bytes: &[u8]) -> f64 {
+    vertices(bytes)
+        .chunks_exact(3)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| to_f64(t[i]));
+            dot(a, cross(b, c)) / 6.0
+        })
+        .sum::<f64>()
+        .abs()
+}
+
+/// `--coordinate-system`: which axis points up in the STLs served.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub enum CoordinateSystem {
+    /// OpenSCAD's own convention.
+    #[default]
+    #[serde(rename = "zup")]
+    ZUp,
+    /// For tools that expect Y up (e.g. many game engines and glTF viewers).
+    #[serde(rename = "yup")]
+    YUp,
+}
+
+impl std::str::FromStr for CoordinateSystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "zup" => Ok(CoordinateSystem::ZUp),
+            "yup" => Ok(CoordinateSystem::YUp),
+            other => Err(format!(
+                "unknown coordinate system '{other}' (expected zup or yup)"
+            )),
+        }
+    }
+}
+
+/// Turn a Z-up mesh into a Y-up one: a quarter turn about X, so `(x, y, z)`
+/// becomes `(x, z, -y)`. Being a rotation, it keeps the mesh right-handed
+/// and its facets facing outward.
+pub fn to_y_up(bytes: &[u8]) -> Vec<u8> {
+    // `0.0 - y` rather than `-y`, so ASCII output doesn't gain `-0`s.
+    let turn = |[x, y, z]: Vec3| [x, z, 0.0 - y];
+    transform(bytes, turn, turn)
+}
+
+/// `to_y_up` a file in place.
+pub fn to_y_up_file(path: &Path) -> io::Result<()> {
+    let bytes = std::fs::read(path)?;
+    std::fs::write(path, to_y_up(&bytes))
+}
+
+/// `volume` of a file.
+pub fn volume_file(path: &Path) -> io::Result<f64> {
+    Ok(volume(&std::fs::read(path)?))
+}
+
+/// Multiply every vertex by `factor` (e.g. 1/25.4 ≈ 0.03937 to turn the
+/// millimetres OpenSCAD writes into inches). Normals are unchanged, so
+/// `factor` must be positive.
+pub fn scale(bytes: &[u8], factor: f32) -> Vec<u8> {
+    if is_binary(bytes) {
+        let mut out = bytes.to_vec();
+        for record in out[BINARY_HEADER_LEN..].chunks_exact_mut(BINARY_RECORD_LEN) {
+            for coord in record[12..48].chunks_exact_mut(4) {
+                let v = f32::from_le_bytes(coord.try_into().unwrap()) * factor;
+                coord.copy_from_slice(&v.to_le_bytes());
+            }
+        }
+        return out;
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut out = String::with_capacity(bytes.len());
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match trimmed.strip_prefix("vertex") {
+            Some(rest) => {
+                out.push_str(&line[..line.len() - trimmed.len()]);
+                out.push_str("vertex");
+                for coord in rest.split_whitespace() {
+                    out.push(' ');
+                    match coord.parse::<f32>() {
+                        Ok(v) => out.push_str(&(v * factor).to_string()),
+                        Err(_) => out.push_str(coord),
+                    }
+                }
+            }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+/// `scale` a file in place.
+pub fn scale_file(path: &Path, factor: f32) -> io::Result<()> {
+    let bytes = std::fs::read(path)?;
+    std::fs::write(path, scale(&bytes, factor))
+}
+
+/// Rotate the mesh so its largest flat face lies on the bed: facing down,
+/// with the lowest point at Z=0. Only faces with no geometry beyond their
+/// plane count, since a face inside a pocket can't rest on anything. A mesh
+/// with no such face comes back unchanged.
+pub fn orient_flat(bytes: &[u8]) -> Vec<u8> {
+    let points = vertices(bytes);
+    let Some(down) = largest_outer_face_normal(&points) else {
+        return bytes.to_vec();
+    };
+    let rotation = rotation_onto(down, [0.0, 0.0, -1.0]);
+    let min_z = points
+        .iter()
+        .map(|p| apply(&rotation, to_f64(*p))[2])
+        .fold(f64::INFINITY, f64::min);
+    transform(
+        bytes,
+        |p| {
+            let mut p = apply(&rotation, p);
+            p[2] -= min_z;
+            p
+        },
+        |n| apply(&rotation, n),
+    )
+}
+
+/// `orient_flat` a file in place.
+pub fn orient_flat_file(path: &Path) -> io::Result<()> {
+    let bytes = std::fs::read(path)?;
+    std::fs::write(path, orient_flat(&bytes))
+}
+
+type Vec3 = [f64; 3];
+type Matrix = [Vec3; 3];
+
+/// Planes closer than this (in normal components and in mm) are one face.
+const PLANE_TOLERANCE: f64 = 1e-4;
+
+/// Outward normal of the biggest coplanar group of triangles that has the
+/// whole mesh on one side. Winding isn't trusted: the outward side is the one
+/// with nothing beyond it.
+fn largest_outer_face_normal(points: &[[f32; 3]]) -> Option<Vec3> {
+    // Keyed by the plane (normal and offset) snapped to the tolerance grid.
+    let mut faces: HashMap<[i64; 4], (Vec3, f64, f64)> = HashMap::new();
+    for triangle in points.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| to_f64(triangle[i]));
+        let cross = cross(sub(b, a), sub(c, a));
+        let len = dot(cross, cross).sqrt();
+        if len <= f64::EPSILON {
+            continue;
+        }
+        let normal = cross.map(|x| x / len);
+        let offset = dot(normal, a);
+        let snap = |x: f64| (x / PLANE_TOLERANCE).round() as i64;
+        let key = [
+            snap(normal[0]),
+            snap(normal[1]),
+            snap(normal[2]),
+            snap(offset),
+        ];
+        faces.entry(key).or_insert((normal, offset, 0.0)).2 += len / 2.0;
+    }
+    let mut faces: Vec<_> = faces.into_values().collect();
+    faces.sort_by(|x, y| y.2.total_cmp(&x.2));
+    faces.into_iter().find_map(|(normal, offset, _)| {
+        let beyond = |sign: f64| {
+            points
+                .iter()
+                .any(|p| sign * (dot(normal, to_f64(*p)) - offset) > PLANE_TOLERANCE)
+        };
+        if !beyond(1.0) {
+            Some(normal)
+        } else if !beyond(-1.0) {
+            Some(normal.map(|x| -x))
+        } else {
+            None
+        }
+    })
+}
+
+/// Rotation taking unit vector `from` onto unit vector `to` (Rodrigues).
+fn rotation_onto(from: Vec3, to: Vec3) -> Matrix {
+    let cos = dot(from, to);
+    if cos > 1.0 - 1e-12 {
+        return [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    }
+    let axis = if cos < -1.0 + 1e-12 {
+        // Opposite vectors: turn half way around any perpendicular axis.
+        let other = if from[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        cross(from, other)
+    } else {
+        cross(from, to)
+    };
+    let len = dot(axis, axis).sqrt();
+    let [x, y, z] = axis.map(|v| v / len);
+    let cos = cos.clamp(-1.0, 1.0);
+    let sin = (1.0 - cos * cos).sqrt();
+    let t = 1.0 - cos;
+    [
+        [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y],
+        [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x],
+        [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos],
+    ]
+}
+
+/// Rewrite every vertex with `point` and every facet normal with `normal`,
+/// keeping the file's format.
+fn transform(bytes: &[u8], point: impl Fn(Vec3) -> Vec3, normal: impl Fn(Vec3) -> Vec3) -> Vec<u8> {
+    if is_binary(bytes) {
+        let mut out = bytes.to_vec();
+        for record in out[BINARY_HEADER_LEN..].chunks_exact_mut(BINARY_RECORD_LEN) {
+            for (i, chunk) in record[..48].chunks_exact_mut(12).enumerate() {
+                let v = [0, 4, 8].map(|j| f32::from_le_bytes(chunk[j..j + 4].try_into().unwrap()));
+                let v = if i == 0 {
+                    normal(to_f64(v))
+                } else {
+                    point(to_f64(v))
+                };
+                for (j, c) in v.into_iter().enumerate() {
+                    chunk[j * 4..j * 4 + 4].copy_from_slice(&(c as f32).to_le_bytes());
+                }
+            }
+        }
+        return out;
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut out = String::with_capacity(bytes.len());
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let (keyword, rest, f): (_, _, &dyn Fn(Vec3) -> Vec3) =
+            if let Some(rest) = trimmed.strip_prefix("facet normal") {
+                ("facet normal", rest, &normal)
+            } else if let Some(rest) = trimmed.strip_prefix("vertex") {
+                ("vertex", rest, &point)
+            } else {
+                out.push_str(line);
+                out.push('\n');
+                continue;
+            };
+        let coords: Vec<f64> = rest
+            .split_whitespace()
+            .filter_map(|c| c.parse().ok())
+            .collect();
+        out.push_str(&line[..line.len() - trimmed.len()]);
+        out.push_str(keyword);
+        match <[f64; 3]>::try_from(coords) {
+            Ok(v) => {
+                for c in f(v) {
+                    out.push(' ');
+                    out.push_str(&(c as f32).to_string());
+                }
+            }
+            Err(_) => out.push_str(rest),
+        }
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+fn to_f64(v: [f32; 3]) -> Vec3 {
+    v.map(f64::from)
+}
+
+fn apply(m: &Matrix, v: Vec3) -> Vec3 {
+    m.map(|row| dot(row, v))
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// A binary STL holding one triangle in the XY plane, for stand-in output.
+pub fn single_triangle() -> Vec<u8> {
+    let mut out = vec![0u8; 80];
+    out.extend_from_slice(&1u32.to_le_bytes());
+    // Normal, then the three vertices.
+    let floats: [f32; 12] = [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 10.0, 0.0, 0.0, 0.0, 10.0, 0.0];
+    for v in floats {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out.extend_from_slice(&[0, 0]);
+    out
+}
+
+/// Reorder triangles into a canonical order so that two meshes with the
+/// same triangles produce identical bytes regardless of emission order.
+///
+/// This only sorts whole triangles: it won't reconcile vertex rotation within
+/// a triangle or floating point differences, and it costs a full sort of the
+/// mesh, so it is only applied when `--deterministic` is requested.
+pub fn canonicalize(bytes: &[u8]) -> Vec<u8> {
+    if is_binary(bytes) {
+        canonicalize_binary(bytes)
+    } else {
+        canonicalize_ascii(bytes)
+    }
+}
+
+fn canonicalize_binary(bytes: &[u8]) -> Vec<u8> {
+    let mut records: Vec<&[u8]> = bytes[BINARY_HEADER_LEN..]
+        .chunks_exact(BINARY_RECORD_LEN)
+        .collect();
+    records.sort_unstable();
+
+    let mut out = Vec::with_capacity(bytes.len());
+    // The header comment may carry a timestamp or tool name; blank it.
+    out.extend_from_slice(&[0u8; 80]);
+    out.extend_from_slice(&bytes[80..BINARY_HEADER_LEN]);
+    for record in records {
+        out.extend_from_slice(record);
+    }
+    out
+}
+
+fn canonicalize_ascii(bytes: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = text.lines();
+
+    let Some(solid) = lines.next() else {
+        return bytes.to_vec();
+    };
+
+    // Group lines into facet blocks: "facet normal ..." through "endfacet".
+    let mut facets: Vec<String> = Vec::new();
+    let mut trailer: Vec<&str> = Vec::new();
+    let mut current: Option<String> = None;
+    for line in lines {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("facet") {
+            current = Some(String::new());
+        }
+        match current.as_mut() {
+            Some(block) => {
+                block.push_str(line);
+                block.push('\n');
+                if trimmed.starts_with("endfacet") {
+                    facets.extend(current.take());
+                }
+            }
+            None => trailer.push(line),
+        }
+    }
+    facets.sort_unstable();
+
+    let mut out = String::with_capacity(bytes.len());
+    out.push_str(solid);
+    out.push('\n');
+    for facet in &facets {
+        out.push_str(facet);
+    }
+    for line in trailer {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ascii_facet(z: f32) -> String {
+        format!(
+            "  facet normal 0 0 1\n    outer loop\n      vertex 0 0 {z}\n      vertex 1 0 {z}\n      vertex 0 1 {z}\n    endloop\n  endfacet\n"
+        )
+    }
+
+    fn binary_stl(zs: &[f32]) -> Vec<u8> {
+        let mut out = vec![b'x'; 80];
+        out.extend_from_slice(&(zs.len() as u32).to_le_bytes());
+        for z in zs {
+            for v in [0.0, 0.0, 1.0, 0.0, 0.0, *z, 1.0, 0.0, *z, 0.0, 1.0, *z] {
+                out.extend_from_slice(&f32::to_le_bytes(v));
+            }
+            out.extend_from_slice(&[0, 0]);
+        }
+        out
+    }
+
+    #[test]
+    fn triangle_count_reads_binary_header() {
+        let stl = binary_stl(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(triangle_count(&stl), 5);
+        assert_eq!(triangle_count(&binary_stl(&[])), 0);
+    }
+
+    #[test]
+    fn triangle_count_counts_ascii_facets() {
+        let stl = format!(
+            "solid OpenSCAD_Model\n{}{}endsolid OpenSCAD_Model\n",
+            ascii_facet(1.0),
+            ascii_facet(2.0)
+        );
+        assert_eq!(triangle_count(stl.as_bytes()), 2);
+    }
+
+    #[test]
+    fn triangle_count_file_matches_in_memory_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let ascii = format!(
+            "solid x\n{}{}{}endsolid x\n",
+            ascii_facet(1.0),
+            ascii_facet(2.0),
+            ascii_facet(3.0)
+        );
+        let cases = [
+            ("a.stl", ascii.into_bytes(), 3),
+            ("b.stl", binary_stl(&[1.0, 2.0]), 2),
+            ("c.stl", b"solid x\nendsolid x\n".to_vec(), 0),
+        ];
+        for (name, bytes, expected) in cases {
+            let path = dir.path().join(name);
+            std::fs::write(&path, &bytes).unwrap();
+            assert_eq!(triangle_count_file(&path).unwrap(), expected, "{name}");
+            assert_eq!(triangle_count(&bytes), expected, "{name}");
+        }
+    }
+
+    /// A unit cube from the origin as 12 triangles: `(binary, ascii)`.
+    fn unit_cube() -> (Vec<u8>, String) {
+        let corner = |i: usize| [(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32];
+        let faces = [
+            [0, 1, 3, 2],
+            [4, 6, 7, 5],
+            [0, 4, 5, 1],
+            [2, 3, 7, 6],
+            [0, 2, 6, 4],
+            [1, 5, 7, 3],
+        ];
+        let triangles: Vec<[[f32; 3]; 3]> = faces
+            .iter()
+            .flat_map(|f| [[f[0], f[1], f[2]], [f[0], f[2], f[3]]])
+            .map(|t| t.map(corner))
+            .collect();
+
+        let mut binary = vec![0u8; 80];
+        binary.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+        let mut ascii = String::from("solid cube\n");
+        for t in &triangles {
+            binary.extend_from_slice(&[0u8; 12]);
+            ascii.push_str("  facet normal 0 0 0\n    outer loop\n");
+            for v in t {
+                for c in v {
+                    binary.extend_from_slice(&c.to_le_bytes());
+                }
+                ascii.push_str(&format!("      vertex {} {} {}\n", v[0], v[1], v[2]));
+            }
+            binary.extend_from_slice(&[0, 0]);
+            ascii.push_str("    endloop\n  endfacet\n");
+        }
+        ascii.push_str("endsolid cube\n");
+        (binary, ascii)
+    }
+
+    #[test]
+    fn scaling_a_cube_by_two_doubles_its_bounds() {
+        let (binary, ascii) = unit_cube();
+        for stl in [binary, ascii.into_bytes()] {
+            assert_eq!(bounds(&stl), Some(([0.0; 3], [1.0; 3])));
+            let scaled = scale(&stl, 2.0);
+            assert_eq!(bounds(&scaled), Some(([0.0; 3], [2.0; 3])));
+            assert_eq!(triangle_count(&scaled), 12);
+            assert_eq!(is_binary(&scaled), is_binary(&stl));
+        }
+        assert_eq!(bounds(b"solid x\nendsolid x\n"), None);
+    }
+
+    #[test]
+    fn orient_flat_lays_a_tilted_box_on_its_largest_face() {
+        // A 4 x 2 x 0.5 slab, tilted about two axes and lifted off the bed.
+        let (cube, _) = unit_cube();
+        let (a, b) = (0.5f64, 0.3f64);
+        let tilt = |p: [f32; 3]| {
+            let [x, y, z] = [p[0] as f64 * 4.0, p[1] as f64 * 2.0, p[2] as f64 * 0.5];
+            let (y, z) = (y * a.cos() - z * a.sin(), y * a.sin() + z * a.cos());
+            let (x, z) = (x * b.cos() + z * b.sin(), -x * b.sin() + z * b.cos());
+            [x as f32 + 7.0, y as f32 - 3.0, z as f32 + 5.0]
+        };
+        let mut tilted = cube[..BINARY_HEADER_LEN].to_vec();
+        for record in cube[BINARY_HEADER_LEN..].chunks_exact(BINARY_RECORD_LEN) {
+            tilted.extend_from_slice(&[0u8; 12]);
+            for v in record[12..48].chunks_exact(12) {
+                let p = [0, 4, 8].map(|i| f32::from_le_bytes(v[i..i + 4].try_into().unwrap()));
+                for c in tilt(p) {
+                    tilted.extend_from_slice(&c.to_le_bytes());
+                }
+            }
+            tilted.extend_from_slice(&[0, 0]);
+        }
+        let ascii = {
+            let mut text = String::from("solid slab\n");
+            for t in vertices(&tilted).chunks_exact(3) {
+                text.push_str("  facet normal 0 0 0\n    outer loop\n");
+                for v in t {
+                    text.push_str(&format!("      vertex {} {} {}\n", v[0], v[1], v[2]));
+                }
+                text.push_str("    endloop\n  endfacet\n");
+            }
+            text.push_str("endsolid slab\n");
+            text.into_bytes()
+        };
+
+        for stl in [tilted, ascii] {
+            let flat = orient_flat(&stl);
+            assert_eq!(is_binary(&flat), is_binary(&stl));
+            let zs: Vec<f32> = vertices(&flat).iter().map(|v| v[2]).collect();
+            assert_eq!(zs.len(), 36);
+            // Every corner is on the bed or at the slab's thickness.
+            for z in zs {
+                assert!(z.abs() < 1e-4 || (z - 0.5).abs() < 1e-4, "z = {z}");
+            }
+        }
+    }
+
+    #[test]
+    fn unit_cube_has_unit_volume() {
+        let (binary, ascii) = unit_cube();
+        for stl in [binary, ascii.into_bytes()] {
+            assert!((volume(&stl) - 1.0).abs() < 1e-9);
+            assert!((volume(&scale(&stl, 2.0)) - 8.0).abs() < 1e-9);
+        }
+        assert_eq!(volume(&single_triangle()), 0.0);
+    }
+
+    #[test]
+    fn y_up_maps_z_onto_y() {
+        let ascii = "solid t\n  facet normal 0 0 1\n    outer loop\n      vertex 0 0 1\n      vertex 1 0 1\n      vertex 0 1 1\n    endloop\n  endfacet\nendsolid t\n";
+        for stl in [single_triangle(), ascii.as_bytes().to_vec()] {
+            let before = vertices(&stl);
+            let after = vertices(&to_y_up(&stl));
+            for (b, a) in before.iter().zip(&after) {
+                assert_eq!(*a, [b[0], b[2], -b[1]]);
+            }
+        }
+        let up = vertices(&to_y_up(ascii.as_bytes()));
+        assert_eq!(up[0], [0.0, 1.0, 0.0]);
+        assert!(
+            String::from_utf8(to_y_up(ascii.as_bytes()))
+                .unwrap()
+                .contains("facet normal 0 1 0\n")
+        );
+        assert_eq!("yup".parse(), Ok(CoordinateSystem::YUp));
+        assert!("xup".parse::<CoordinateSystem>().is_err());
+    }
+
+    #[test]
+    fn single_triangle_is_a_binary_stl() {
+        let stl = single_triangle();
+        assert!(is_binary(&stl));
+        assert_eq!(stl.len(), BINARY_HEADER_LEN + BINARY_RECORD_LEN);
+        assert_eq!(triangle_count(&stl), 1);
+    }
+
+    #[test]
+    fn canonicalize_ascii_is_stable_for_shuffled_input() {
+        let a = format!(
+            "solid OpenSCAD_Model\n{}{}{}endsolid OpenSCAD_Model\n",
+            ascii_facet(1.0),
+            ascii_facet(2.0),
+            ascii_facet(3.0)
+        );
+        let b = format!(
+            "solid OpenSCAD_Model\n{}{}{}endsolid OpenSCAD_Model\n",
+            ascii_facet(3.0),
+            ascii_facet(1.0),
+            ascii_facet(2.0)
+        );
+        assert_ne!(a, b);
+        let ca = canonicalize(a.as_bytes());
+        let cb = canonicalize(b.as_bytes());
+        assert_eq!(ca, cb);
+        assert_eq!(canonicalize(&ca), ca, "canonical output is a fixed point");
+
+        let text = String::from_utf8(ca).unwrap();
+        assert!(text.starts_with("solid OpenSCAD_Model\n"));
+        assert!(text.ends_with("endsolid OpenSCAD_Model\n"));
+        assert_eq!(text.matches("endfacet").count(), 3);
+    }
+
+    #[test]
+    fn canonicalize_binary_is_stable_for_shuffled_input() {
+        let a = binary_stl(&[1.0, 2.0, 3.0]);
+        let b = binary_stl(&[2.0, 3.0, 1.0]);
+        assert!(is_binary(&a));
+        assert_ne!(a, b);
+
+        let ca = canonicalize(&a);
+        assert_eq!(ca, canonicalize(&b));
+        assert_eq!(ca.len(), a.len());
+        assert!(ca[..80].iter().all(|&b| b == 0), "header is blanked");
+    }
+}
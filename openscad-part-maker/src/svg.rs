@@ -0,0 +1,301 @@
+//! Cheap checks on uploaded SVGs before they reach OpenSCAD's `import()`.
+
+use std::fmt;
+
+/// Default for `--max-svg-elements`.
+pub const DEFAULT_MAX_ELEMENTS: usize = 50_000;
+
+/// Elements that become geometry in `import()`; their count drives render time.
+const SHAPE_ELEMENTS: [&str; 8] = [
+    "path", "polygon", "polyline", "rect", "circle", "ellipse", "line", "use",
+];
+
+/// Why an upload was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvgError {
+    /// The bytes don't start like an SVG document; `looks_like` names the
+    /// format when the magic bytes are recognised.
+    NotSvg { looks_like: Option<&'static str> },
+    /// More shape elements than the configured maximum.
+    TooManyElements { max: usize },
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgError::NotSvg {
+                looks_like: Some(kind),
+            } => write!(f, "uploaded file is not an SVG (it looks like {kind})"),
+            SvgError::NotSvg { looks_like: None } => {
+                write!(
+                    f,
+                    "uploaded file is not an SVG (expected <?xml or <svg at the start)"
+                )
+            }
+            SvgError::TooManyElements { max } => {
+                write!(f, "SVG has more than {max} shape elements")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SvgError {}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Magic bytes of formats commonly uploaded by mistake (renamed to `.svg`).
+const KNOWN_MAGIC: [(&[u8], &str); 4] = [
+    (b"\x89PNG", "a PNG image"),
+    (b"\xFF\xD8\xFF", "a JPEG image"),
+    (b"GIF8", "a GIF image"),
+    (b"%PDF", "a PDF document"),
+];
+
+/// Check that `bytes` look like an SVG document (after an optional BOM and
+/// whitespace they must start with `<?xml` or `<svg`) with no more than
+/// `max_elements` shape elements.
+pub fn validate_svg(bytes: &[u8], max_elements: usize) -> Result<(), SvgError> {
+    check_svg_prefix(bytes)?;
+    if count_shape_elements(bytes, max_elements) > max_elements {
+        return Err(SvgError::TooManyElements { max: max_elements });
+    }
+    Ok(())
+}
+
+/// Count start tags of `SHAPE_ELEMENTS` (namespace prefixes ignored), giving
+/// up once the count passes `stop_after`.
+pub fn count_shape_elements(bytes: &[u8], stop_after: usize) -> usize {
+    let mut count = 0;
+    for (idx, _) in bytes.iter().enumerate().filter(|(_, b)| **b == b'<') {
+        let rest = &bytes[idx + 1..];
+        let len = rest
+            .iter()
+            .position(|b| !(b.is_ascii_alphanumeric() || matches!(b, b':' | b'-' | b'_')))
+            .unwrap_or(rest.len());
+        let name = &rest[..len];
+        let local = match name.iter().rposition(|b| *b == b':') {
+            Some(colon) => &name[colon + 1..],
+            None => name,
+        };
+        if SHAPE_ELEMENTS.iter().any(|el| el.as_bytes() == local) {
+            count += 1;
+            if count > stop_after {
+                break;
+            }
+        }
+    }
+    count
+}
+
+/// Millimetres per CSS pixel: OpenSCAD's `import()` reads SVGs at 96 dpi.
+const MM_PER_PX: f64 = 25.4 / 96.0;
+
+/// Size of the drawing in millimetres, as OpenSCAD's `import()` sizes it:
+/// the root element's `width` and `height` (px, mm, cm, in, pt or pc; no
+/// unit means px), or its `viewBox` extent in px when those are missing or
+/// relative (`100%`). `None` if neither gives a positive size.
+pub fn parse_svg_dimensions(bytes: &[u8]) -> Option<(f64, f64)> {
+    let text = String::from_utf8_lossy(bytes);
+    let tag = root_svg_tag(&text)?;
+    let size = |name| attribute(tag, name).and_then(length_mm);
+    if let (Some(width), Some(height)) = (size("width"), size("height")) {
+        return Some((width, height));
+    }
+    let view_box: Vec<f64> = attribute(tag, "viewBox")?
+        .split([' ', ','])
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    match view_box[..] {
+        [_, _, width, height] if width > 0.0 && height > 0.0 => {
+            Some((width * MM_PER_PX, height * MM_PER_PX))
+        }
+        _ => None,
+    }
+}
+
+/// The attributes of the first `<svg ...>` start tag (prefix-less).
+fn root_svg_tag(text: &str) -> Option<&str> {
+    let start = text.match_indices("<svg").find_map(|(idx, _)| {
+        let after = &text[idx + "<svg".len()..];
+        after
+            .starts_with(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/')
+            .then_some(after)
+    })?;
+    Some(&start[..start.find('>')?])
+}
+
+/// Value of `name="..."` (or single-quoted) within a start tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    tag.match_indices(name).find_map(|(idx, _)| {
+        let before = tag[..idx].chars().next_back()?;
+        if !before.is_ascii_whitespace() {
+            return None;
+        }
+        let rest = tag[idx + name.len()..].trim_start().strip_prefix('=')?;
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|q| matches!(q, '"' | '\''))?;
+        let value = &rest[1..];
+        Some(&value[..value.find(quote)?])
+    })
+}
+
+/// An SVG length like `120`, `120px` or `42.5mm`, in millimetres.
+fn length_mm(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let mm_per_unit = match unit.trim() {
+        "" | "px" => MM_PER_PX,
+        "mm" => 1.0,
+        "cm" => 10.0,
+        "in" => 25.4,
+        "pt" => 25.4 / 72.0,
+        "pc" => 25.4 / 6.0,
+        _ => return None,
+    };
+    (number > 0.0 && number.is_finite()).then_some(number * mm_per_unit)
+}
+
+fn check_svg_prefix(bytes: &[u8]) -> Result<(), SvgError> {
+    let body = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    let start = body
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(body.len());
+    let body = &body[start..];
+    if body.starts_with(b"<?xml") || body.starts_with(b"<svg") {
+        return Ok(());
+    }
+    let looks_like = KNOWN_MAGIC
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+        .map(|(_, kind)| *kind);
+    Err(SvgError::NotSvg { looks_like })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_svg_with_and_without_prolog() {
+        assert_eq!(
+            validate_svg(
+                b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>",
+                DEFAULT_MAX_ELEMENTS
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            validate_svg(b"\xEF\xBB\xBF  \n<svg></svg>", DEFAULT_MAX_ELEMENTS),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_png_upload() {
+        let err = validate_svg(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", DEFAULT_MAX_ELEMENTS).unwrap_err();
+        assert_eq!(
+            err,
+            SvgError::NotSvg {
+                looks_like: Some("a PNG image")
+            }
+        );
+        assert!(err.to_string().contains("PNG"));
+    }
+
+    #[test]
+    fn rejects_jpeg_upload() {
+        let err = validate_svg(b"\xFF\xD8\xFF\xE0\0\x10JFIF", DEFAULT_MAX_ELEMENTS).unwrap_err();
+        assert!(err.to_string().contains("JPEG"));
+    }
+
+    fn svg_with_paths(n: usize) -> String {
+        let mut svg = String::from("<svg xmlns=\"http://www.w3.org/2000/svg\">\n");
+        for i in 0..n {
+            svg.push_str(&format!("<path d=\"M{i} 0h1v1z\"/>\n"));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    #[test]
+    fn element_limit_allows_count_at_max() {
+        assert_eq!(validate_svg(svg_with_paths(100).as_bytes(), 100), Ok(()));
+    }
+
+    #[test]
+    fn element_limit_rejects_count_over_max() {
+        let err = validate_svg(svg_with_paths(101).as_bytes(), 100).unwrap_err();
+        assert_eq!(err, SvgError::TooManyElements { max: 100 });
+    }
+
+    #[test]
+    fn count_shape_elements_matches_whole_tag_names() {
+        let svg = br#"<svg><defs><linearGradient id="g"/></defs><svg:rect/><line/><g><circle/></g></svg>"#;
+        assert_eq!(count_shape_elements(svg, usize::MAX), 3);
+    }
+
+    fn assert_size(svg: &str, expected: (f64, f64)) {
+        let (width, height) = parse_svg_dimensions(svg.as_bytes()).unwrap();
+        assert!(
+            (width - expected.0).abs() < 1e-9 && (height - expected.1).abs() < 1e-9,
+            "{svg}: got {width} x {height}"
+        );
+    }
+
+    #[test]
+    fn dimensions_in_px_are_converted_at_96_dpi() {
+        assert_size(r#"<svg width="96" height="48px">"#, (25.4, 12.7));
+        assert_size(
+            "<?xml version=\"1.0\"?>\n<svg\n  xmlns=\"http://www.w3.org/2000/svg\"\n  width='192px' height='96'/>",
+            (50.8, 25.4),
+        );
+    }
+
+    #[test]
+    fn dimensions_in_mm_are_kept() {
+        assert_size(
+            r#"<svg width="80mm" height="4.5cm" viewBox="0 0 10 10">"#,
+            (80.0, 45.0),
+        );
+        assert_size(r#"<svg width="1in" height="72pt">"#, (25.4, 25.4));
+    }
+
+    #[test]
+    fn viewbox_is_used_without_absolute_dimensions() {
+        assert_size(r#"<svg viewBox="0 0 96 192">"#, (25.4, 50.8));
+        assert_size(
+            r#"<svg width="100%" height="100%" viewBox="-5,-5, 48,24">"#,
+            (12.7, 6.35),
+        );
+        // The root's own attributes, not a nested element's or a stroke-width.
+        assert_size(
+            r#"<svg stroke-width="3" viewBox="0 0 96 96"><svg width="1mm" height="1mm"/></svg>"#,
+            (25.4, 25.4),
+        );
+    }
+
+    #[test]
+    fn dimensions_are_unknown_without_a_usable_size() {
+        for svg in [
+            "<svg>",
+            r#"<svg width="10" >"#,
+            r#"<svg viewBox="0 0 0 10">"#,
+            r#"<svg width="10em" height="10em">"#,
+            "not svg",
+        ] {
+            assert_eq!(parse_svg_dimensions(svg.as_bytes()), None, "{svg}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_text() {
+        let err = validate_svg(b"hello world", DEFAULT_MAX_ELEMENTS).unwrap_err();
+        assert_eq!(err, SvgError::NotSvg { looks_like: None });
+    }
+}
@@ -0,0 +1,80 @@
+//! `--max-render-per-minute`: a token bucket shared by every client, so a
+//! small host isn't swamped however the load is spread.
+//!
+//! The bucket holds up to a minute's worth of renders and refills
+//! continuously; a render that finds it empty is turned away with how long
+//! until the next token.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+pub struct RenderThrottle {
+    per_minute: u32,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RenderThrottle {
+    /// `per_minute` must be at least 1.
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(per_minute),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token for one render, or say how long until one is free.
+    pub fn try_take(&self) -> Result<(), Duration> {
+        self.try_take_at(Instant::now())
+    }
+
+    fn try_take_at(&self, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(self.per_minute);
+        let per_sec = capacity / 60.0;
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * per_sec).min(capacity);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_empties_then_refills_over_the_minute() {
+        let throttle = RenderThrottle::new(2);
+        let start = Instant::now();
+        assert!(throttle.try_take_at(start).is_ok());
+        assert!(throttle.try_take_at(start).is_ok());
+        let wait = throttle.try_take_at(start).unwrap_err();
+        assert_eq!(wait.as_secs(), 30);
+
+        // Half a minute later one token has come back, not two.
+        let later = start + Duration::from_secs(30);
+        assert!(throttle.try_take_at(later).is_ok());
+        assert!(throttle.try_take_at(later).is_err());
+
+        // An idle hour doesn't bank more than a minute's worth.
+        let idle = later + Duration::from_secs(3600);
+        assert!(throttle.try_take_at(idle).is_ok());
+        assert!(throttle.try_take_at(idle).is_ok());
+        assert!(throttle.try_take_at(idle).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Resumable SVG uploads: a subset of the tus protocol (creation, `HEAD`
+//! and `PATCH`), for clients on flaky networks. A finished upload is
+//! rendered by naming its id in a render form's `svg_upload` field.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Seek, SeekFrom, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tempfile::NamedTempFile;
+
+/// How long an upload is kept after it was last created or appended to.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Most uploads held at once; more are refused until some finish or
+/// expire.
+pub const DEFAULT_MAX_ENTRIES: usize = 32;
+
+/// Why an upload request was refused.
+#[derive(Debug)]
+pub enum UploadError {
+    /// No such upload, or it expired.
+    NotFound,
+    /// The declared length is over the cap.
+    TooLarge {
+        max: u64,
+    },
+    /// Already holding as many unfinished uploads as allowed.
+    Full {
+        max: usize,
+    },
+    /// A `PATCH` that doesn't start where the upload left off.
+    OffsetMismatch {
+        expected: u64,
+    },
+    /// A `PATCH` running past the declared length.
+    PastEnd {
+        length: u64,
+    },
+    /// Rendering an upload that hasn't received all its bytes.
+    Incomplete {
+        offset: u64,
+        length: u64,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::NotFound => write!(f, "unknown or expired upload"),
+            UploadError::TooLarge { max } => write!(f, "uploads are limited to {max} bytes"),
+            UploadError::Full { max } => {
+                write!(f, "{max} uploads are already in progress; try again later")
+            }
+            UploadError::OffsetMismatch { expected } => {
+                write!(
+                    f,
+                    "Upload-Offset must be {expected}, where the upload left off"
+                )
+            }
+            UploadError::PastEnd { length } => {
+                write!(f, "the chunk runs past the upload's {length} bytes")
+            }
+            UploadError::Incomplete { offset, length } => {
+                write!(f, "the upload is incomplete ({offset} of {length} bytes)")
+            }
+            UploadError::Io(err) => write!(f, "upload storage failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+impl From<io::Error> for UploadError {
+    fn from(err: io::Error) -> Self {
+        UploadError::Io(err)
+    }
+}
+
+/// Where an upload stands: `offset` of `length` bytes received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadProgress {
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug)]
+struct Partial {
+    /// Bytes so far, in a temp file removed when the entry is dropped.
+    file: NamedTempFile,
+    progress: UploadProgress,
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Locked on its own, so one client's disk write doesn't hold up the
+    /// others' uploads.
+    partial: Arc<Mutex<Partial>>,
+    touched_at: Instant,
+}
+
+/// Upload id -> partial file map. Expired entries are dropped lazily, like
+/// `DownloadStore`'s.
+#[derive(Debug)]
+pub struct UploadStore {
+    entries: Mutex<HashMap<String, Entry>>,
+    ttl: Duration,
+    max_bytes: u64,
+    max_entries: usize,
+}
+
+impl UploadStore {
+    pub fn new(ttl: Duration, max_bytes: u64, max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::default(),
+            ttl,
+            max_bytes,
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    /// Start an upload of `length` bytes and return its id.
+    pub fn create(&self, length: u64) -> Result<String, UploadError> {
+        self.create_at(length, Instant::now())
+    }
+
+    /// How far the upload has got, for a client resuming it.
+    pub fn progress(&self, id: &str) -> Result<UploadProgress, UploadError> {
+        self.progress_at(id, Instant::now())
+    }
+
+    /// Write `chunk` at `offset`, which must be the upload's current offset.
+    /// Returns the new offset. Blocks on file I/O.
+    pub fn append(&self, id: &str, offset: u64, chunk: &[u8]) -> Result<u64, UploadError> {
+        self.append_at(id, offset, chunk, Instant::now())
+    }
+
+    /// Remove a finished upload and return its bytes. Blocks on file I/O.
+    pub fn take_complete(&self, id: &str) -> Result<Vec<u8>, UploadError> {
+        self.take_complete_at(id, Instant::now())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn create_at(&self, length: u64, now: Instant) -> Result<String, UploadError> {
+        if length > self.max_bytes {
+            return Err(UploadError::TooLarge {
+                max: self.max_bytes,
+            });
+        }
+        let file = tempfile::Builder::new()
+            .prefix("openscad-upload-")
+            .tempfile()?;
+        let id = crate::downloads::new_token();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, e| now.duration_since(e.touched_at) < self.ttl);
+        // Live uploads are never evicted, or anyone could wipe the others'
+        // by starting enough empty ones.
+        if entries.len() >= self.max_entries {
+            return Err(UploadError::Full {
+                max: self.max_entries,
+            });
+        }
+        let partial = Partial {
+            file,
+            progress: UploadProgress { offset: 0, length },
+        };
+        entries.insert(
+            id.clone(),
+            Entry {
+                partial: Arc::new(Mutex::new(partial)),
+                touched_at: now,
+            },
+        );
+        Ok(id)
+    }
+
+    fn progress_at(&self, id: &str, now: Instant) -> Result<UploadProgress, UploadError> {
+        let partial = self.live_partial(id, now, false)?;
+        let progress = partial.lock().unwrap().progress;
+        Ok(progress)
+    }
+
+    fn append_at(
+        &self,
+        id: &str,
+        offset: u64,
+        chunk: &[u8],
+        now: Instant,
+    ) -> Result<u64, UploadError> {
+        let partial = self.live_partial(id, now, true)?;
+        let mut partial = partial.lock().unwrap();
+        let UploadProgress {
+            offset: expected,
+            length,
+        } = partial.progress;
+        if offset != expected {
+            return Err(UploadError::OffsetMismatch { expected });
+        }
+        if offset + chunk.len() as u64 > length {
+            return Err(UploadError::PastEnd { length });
+        }
+        let file = partial.file.as_file_mut();
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(chunk)?;
+        partial.progress.offset += chunk.len() as u64;
+        Ok(partial.progress.offset)
+    }
+
+    fn take_complete_at(&self, id: &str, now: Instant) -> Result<Vec<u8>, UploadError> {
+        let shared = self.live_partial(id, now, false)?;
+        let mut partial = shared.lock().unwrap();
+        let UploadProgress { offset, length } = partial.progress;
+        if offset < length {
+            return Err(UploadError::Incomplete { offset, length });
+        }
+        // Only one taker gets the bytes; a racing one finds it gone.
+        {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get(id) {
+                Some(entry) if Arc::ptr_eq(&entry.partial, &shared) => entries.remove(id),
+                _ => return Err(UploadError::NotFound),
+            };
+        }
+        let file = partial.file.as_file_mut();
+        file.seek(SeekFrom::Start(0))?;
+        let mut bytes = Vec::with_capacity(length as usize);
+        file.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// The partial upload for `id`, dropping it instead if it has expired;
+    /// `touch` restarts its TTL. The store lock is released before the
+    /// caller locks the upload itself.
+    fn live_partial(
+        &self,
+        id: &str,
+        now: Instant,
+        touch: bool,
+    ) -> Result<Arc<Mutex<Partial>>, UploadError> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries
+            .get(id)
+            .is_some_and(|e| now.duration_since(e.touched_at) >= self.ttl)
+        {
+            entries.remove(id);
+        }
+        let entry = entries.get_mut(id).ok_or(UploadError::NotFound)?;
+        if touch {
+            entry.touched_at = now;
+        }
+        Ok(entry.partial.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> UploadStore {
+        UploadStore::new(DEFAULT_TTL, 1024, DEFAULT_MAX_ENTRIES)
+    }
+
+    #[test]
+    fn chunks_append_at_the_current_offset() {
+        let store = store();
+        let id = store.create(10).unwrap();
+        assert_eq!(id.len(), 32);
+        assert_eq!(
+            store.progress(&id).unwrap(),
+            UploadProgress {
+                offset: 0,
+                length: 10
+            }
+        );
+
+        assert_eq!(store.append(&id, 0, b"<svg").unwrap(), 4);
+        // A resent chunk, or one skipping ahead, is refused; the offset stays.
+        for offset in [0, 6] {
+            let err = store.append(&id, offset, b"/>").unwrap_err();
+            assert!(
+                matches!(err, UploadError::OffsetMismatch { expected: 4 }),
+                "{err}"
+            );
+        }
+        let err = store.append(&id, 4, b" width=1/>").unwrap_err();
+        assert!(matches!(err, UploadError::PastEnd { length: 10 }), "{err}");
+        assert_eq!(store.progress(&id).unwrap().offset, 4);
+
+        assert_eq!(store.append(&id, 4, b"/>").unwrap(), 6);
+        assert_eq!(store.append(&id, 6, b"\n\n\n\n").unwrap(), 10);
+        assert_eq!(store.take_complete(&id).unwrap(), b"<svg/>\n\n\n\n");
+        assert!(matches!(store.progress(&id), Err(UploadError::NotFound)));
+    }
+
+    #[test]
+    fn incomplete_uploads_cannot_be_taken() {
+        let store = store();
+        let id = store.create(8).unwrap();
+        store.append(&id, 0, b"<svg").unwrap();
+        let err = store.take_complete(&id).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                UploadError::Incomplete {
+                    offset: 4,
+                    length: 8
+                }
+            ),
+            "{err}"
+        );
+        // Still there to resume.
+        assert_eq!(store.append(&id, 4, b"/>  ").unwrap(), 8);
+        assert!(store.take_complete(&id).is_ok());
+    }
+
+    #[test]
+    fn lengths_over_the_cap_are_refused() {
+        let store = UploadStore::new(DEFAULT_TTL, 100, 4);
+        assert!(store.create(100).is_ok());
+        assert!(matches!(
+            store.create(101),
+            Err(UploadError::TooLarge { max: 100 })
+        ));
+    }
+
+    #[test]
+    fn uploads_expire_after_their_last_chunk() {
+        let store = UploadStore::new(Duration::from_secs(60), 1024, 8);
+        let start = Instant::now();
+        let id = store.create_at(4, start).unwrap();
+        let later = start + Duration::from_secs(50);
+        store.append_at(&id, 0, b"<sv", later).unwrap();
+        // 100s after creation, but only 50s after the last chunk.
+        let now = start + Duration::from_secs(100);
+        assert_eq!(store.progress_at(&id, now).unwrap().offset, 3);
+
+        let expired = later + Duration::from_secs(60);
+        assert!(matches!(
+            store.append_at(&id, 3, b"g", expired),
+            Err(UploadError::NotFound)
+        ));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn new_uploads_are_refused_when_full() {
+        let store = UploadStore::new(Duration::from_secs(60), 1024, 2);
+        let start = Instant::now();
+        let a = store.create_at(1, start).unwrap();
+        let b = store.create_at(1, start + Duration::from_secs(30)).unwrap();
+        // Live uploads are kept; the newcomer is turned away.
+        let err = store
+            .create_at(1, start + Duration::from_secs(40))
+            .unwrap_err();
+        assert!(matches!(err, UploadError::Full { max: 2 }), "{err}");
+        assert!(store.progress(&a).is_ok());
+        assert!(store.progress(&b).is_ok());
+
+        // Once `a` expires there is room again.
+        let later = start + Duration::from_secs(70);
+        let c = store.create_at(1, later).unwrap();
+        assert!(store.progress_at(&a, later).is_err());
+        assert!(store.progress_at(&b, later).is_ok());
+        assert!(store.progress_at(&c, later).is_ok());
+    }
+
+    #[test]
+    fn a_write_in_progress_only_holds_up_its_own_upload() {
+        let store = store();
+        let a = store.create(4).unwrap();
+        let b = store.create(4).unwrap();
+        let held = store.entries.lock().unwrap()[&a].partial.clone();
+        let _writing = held.lock().unwrap();
+        assert_eq!(store.append(&b, 0, b"<svg").unwrap(), 4);
+        assert_eq!(store.take_complete(&b).unwrap(), b"<svg");
+        assert_eq!(store.len(), 1);
+    }
+}
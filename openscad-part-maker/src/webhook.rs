@@ -0,0 +1,123 @@
+//! `--on-render`: POST a JSON event to a URL after each successful render.
+//!
+//! Delivery runs in the background so it never delays the response, and a
+//! failed delivery is only logged. Plain `http://` URLs only.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::{Context, bail};
+use axum::http::{Request, StatusCode, Uri, header};
+use http_body_util::Full;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpStream;
+
+use crate::prelude::*;
+use crate::scad_params::ScadParams;
+
+/// Give up on a delivery (connect, send and response) after this long.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body of the webhook POST.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct RenderEvent {
+    /// Download file name.
+    pub filename: String,
+    /// Size of the rendered file.
+    pub bytes: u64,
+    /// Param values as OpenSCAD literals, without the server-side SVG paths.
+    pub params: BTreeMap<String, String>,
+    pub duration_ms: u64,
+}
+
+impl RenderEvent {
+    pub fn new(filename: &str, bytes: u64, params: &ScadParams, duration: Duration) -> Self {
+        Self {
+            filename: filename.to_string(),
+            bytes,
+            params: params
+                .values
+                .iter()
+                .filter(|(name, _)| !params.is_path_define(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+/// Parse an `--on-render` URL: `http://host[:port]/path`.
+pub fn parse_url(url: &str) -> anyhow::Result<Uri> {
+    let uri: Uri = url
+        .parse()
+        .with_context(|| format!("invalid URL {url:?}"))?;
+    if uri.scheme_str() != Some("http") {
+        bail!("{url}: only http:// URLs are supported");
+    }
+    if uri.host().is_none() {
+        bail!("{url}: missing host");
+    }
+    Ok(uri)
+}
+
+/// Deliver `event` to `url` in the background, logging the outcome.
+pub fn spawn_post(url: Uri, event: RenderEvent) {
+    tokio::spawn(async move {
+        match tokio::time::timeout(DELIVERY_TIMEOUT, post(&url, &event)).await {
+            Ok(Ok(status)) if status.is_success() => debug!("Render event sent to {url}"),
+            Ok(Ok(status)) => warn!("Render event to {url} was answered with {status}"),
+            Ok(Err(err)) => warn!("Failed to send render event to {url}: {err:#}"),
+            Err(_) => warn!("Render event to {url} timed out after {DELIVERY_TIMEOUT:?}"),
+        }
+    });
+}
+
+async fn post(url: &Uri, event: &RenderEvent) -> anyhow::Result<StatusCode> {
+    let host = url.host().context("missing host")?;
+    let port = url.port_u16().unwrap_or(80);
+    let stream = TcpStream::connect((host, port))
+        .await
+        .with_context(|| format!("connect to {host}:{port}"))?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(conn);
+
+    let authority = url.authority().map(|a| a.as_str()).unwrap_or(host);
+    let path = url.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let body = serde_json::to_vec(event)?;
+    let request = Request::post(path)
+        .header(header::HOST, authority)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(bytes::Bytes::from(body)))?;
+    Ok(sender.send_request(request).await?.status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scad_params::ScadParamTemplate;
+
+    #[test]
+    fn event_carries_params_without_svg_paths() {
+        let template =
+            ScadParamTemplate::from_scad_text("COASTER_D = 101.6; // @param\nSVG_PATH = \"\";\n")
+                .unwrap();
+        let mut params = template.instantiate();
+        params.set_string("SVG_PATH", "/tmp/work/input.svg");
+        let event = RenderEvent::new("logo.stl", 684, &params, Duration::from_millis(1250));
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "filename": "logo.stl",
+                "bytes": 684,
+                "params": { "COASTER_D": "101.6" },
+                "duration_ms": 1250,
+            })
+        );
+    }
+
+    #[test]
+    fn only_http_urls_are_accepted() {
+        assert!(parse_url("http://hooks.local:8080/render").is_ok());
+        assert!(parse_url("https://hooks.example.com/").is_err());
+        assert!(parse_url("/relative").is_err());
+    }
+}
@@ -0,0 +1,22 @@
+(function () {
+  const fileInput = document.getElementById('svg');
+  const nameInput = document.getElementById('name');
+  if (!fileInput || !nameInput) return;
+
+  let lastAutoName = "";
+
+  fileInput.addEventListener('change', function () {
+    const file = this.files && this.files[0];
+    if (!file) return;
+
+    const fullName = file.name || "";
+    const dot = fullName.lastIndexOf('.');
+    const base = dot > 0 ? fullName.slice(0, dot) : fullName;
+
+    // Only overwrite if the field is empty or matches the last auto-filled name
+    if (nameInput.value.trim() === "" || nameInput.value === lastAutoName) {
+      nameInput.value = base;
+      lastAutoName = base;
+    }
+  });
+})();